@@ -4,13 +4,16 @@ use glib::prelude::*;
 #[macro_use]
 extern crate gstreamer as gst;
 extern crate gstreamer_audio as gst_audio;
+extern crate gstreamer_audio_sys as gst_audio_sys;
 extern crate gstreamer_base as gst_base;
 extern crate gstreamer_video as gst_video;
+extern crate gstreamer_sys as gst_sys;
 
 #[macro_use]
 extern crate lazy_static;
 extern crate byte_slice_cast;
 
+pub mod meta;
 pub mod ndi;
 mod ndiaudiosrc;
 pub mod ndisys;
@@ -19,6 +22,8 @@ pub mod receiver;
 mod ndivideosink;
 pub mod send;
 mod ndiaudiosink;
+mod ndisink;
+mod ndisrc;
 
 use crate::ndi::*;
 use crate::ndisys::*;
@@ -35,6 +40,44 @@ pub enum TimestampMode {
     Timestamp = 2,
 }
 
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy)]
+#[repr(u32)]
+pub enum RecvColorFormat {
+    Auto = 0,
+    UyvyBgra = 1,
+    BgrxBgra = 2,
+    RgbxRgba = 3,
+    UyvyRgba = 4,
+    Fastest = 5,
+}
+
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy)]
+#[repr(u32)]
+pub enum TimeoutAction {
+    Eos = 0,
+    Error = 1,
+    Reconnect = 2,
+    Wait = 3,
+}
+
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy)]
+#[repr(u32)]
+pub enum SendClockMode {
+    Auto = 0,
+    Video = 1,
+    Audio = 2,
+    Disabled = 3,
+}
+
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy)]
+#[repr(u32)]
+pub enum TimecodeMode {
+    Synthesize = 0,
+    RunningTime = 1,
+    TimeCodeMeta = 2,
+    ReferenceTimestamp = 3,
+}
+
 fn plugin_init(plugin: &gst::Plugin) -> Result<(), glib::BoolError> {
     if !ndi::initialize() {
         return Err(glib_bool_error!("Cannot initialize NDI"));
@@ -44,6 +87,8 @@ fn plugin_init(plugin: &gst::Plugin) -> Result<(), glib::BoolError> {
     ndiaudiosrc::register(plugin)?;
     ndivideosink::register(plugin)?;
     ndiaudiosink::register(plugin)?;
+    ndisink::register(plugin)?;
+    ndisrc::register(plugin)?;
     Ok(())
 }
 
@@ -157,6 +202,410 @@ fn timestamp_mode_get_type() -> glib::Type {
     }
 }
 
+impl glib::translate::ToGlib for TimecodeMode {
+    type GlibType = i32;
+
+    fn to_glib(&self) -> i32 {
+        *self as i32
+    }
+}
+
+impl glib::translate::FromGlib<i32> for TimecodeMode {
+    fn from_glib(value: i32) -> Self {
+        match value {
+            0 => TimecodeMode::Synthesize,
+            1 => TimecodeMode::RunningTime,
+            2 => TimecodeMode::TimeCodeMeta,
+            3 => TimecodeMode::ReferenceTimestamp,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl StaticType for TimecodeMode {
+    fn static_type() -> glib::Type {
+        timecode_mode_get_type()
+    }
+}
+
+impl<'a> glib::value::FromValueOptional<'a> for TimecodeMode {
+    unsafe fn from_value_optional(value: &glib::Value) -> Option<Self> {
+        Some(glib::value::FromValue::from_value(value))
+    }
+}
+
+impl<'a> glib::value::FromValue<'a> for TimecodeMode {
+    unsafe fn from_value(value: &glib::Value) -> Self {
+        use glib::translate::ToGlibPtr;
+
+        glib::translate::from_glib(gobject_sys::g_value_get_enum(value.to_glib_none().0))
+    }
+}
+
+impl glib::value::SetValue for TimecodeMode {
+    unsafe fn set_value(value: &mut glib::Value, this: &Self) {
+        use glib::translate::{ToGlib, ToGlibPtrMut};
+
+        gobject_sys::g_value_set_enum(value.to_glib_none_mut().0, this.to_glib())
+    }
+}
+
+fn timecode_mode_get_type() -> glib::Type {
+    use std::sync::Once;
+    static ONCE: Once = Once::new();
+    static mut TYPE: glib::Type = glib::Type::Invalid;
+
+    ONCE.call_once(|| {
+        use std::ffi;
+        use std::ptr;
+
+        static mut VALUES: [gobject_sys::GEnumValue; 5] = [
+            gobject_sys::GEnumValue {
+                value: TimecodeMode::Synthesize as i32,
+                value_name: b"Synthesize\0" as *const _ as *const _,
+                value_nick: b"synthesize\0" as *const _ as *const _,
+            },
+            gobject_sys::GEnumValue {
+                value: TimecodeMode::RunningTime as i32,
+                value_name: b"Running Time\0" as *const _ as *const _,
+                value_nick: b"running-time\0" as *const _ as *const _,
+            },
+            gobject_sys::GEnumValue {
+                value: TimecodeMode::TimeCodeMeta as i32,
+                value_name: b"Video Time Code Meta\0" as *const _ as *const _,
+                value_nick: b"timecode-meta\0" as *const _ as *const _,
+            },
+            gobject_sys::GEnumValue {
+                value: TimecodeMode::ReferenceTimestamp as i32,
+                value_name: b"NDI Timecode Reference Timestamp\0" as *const _ as *const _,
+                value_nick: b"reference-timestamp\0" as *const _ as *const _,
+            },
+            gobject_sys::GEnumValue {
+                value: 0,
+                value_name: ptr::null(),
+                value_nick: ptr::null(),
+            },
+        ];
+
+        let name = ffi::CString::new("GstNdiTimecodeMode").unwrap();
+        unsafe {
+            let type_ = gobject_sys::g_enum_register_static(name.as_ptr(), VALUES.as_ptr());
+            TYPE = glib::translate::from_glib(type_);
+        }
+    });
+
+    unsafe {
+        assert_ne!(TYPE, glib::Type::Invalid);
+        TYPE
+    }
+}
+
+impl glib::translate::ToGlib for RecvColorFormat {
+    type GlibType = i32;
+
+    fn to_glib(&self) -> i32 {
+        *self as i32
+    }
+}
+
+impl glib::translate::FromGlib<i32> for RecvColorFormat {
+    fn from_glib(value: i32) -> Self {
+        match value {
+            0 => RecvColorFormat::Auto,
+            1 => RecvColorFormat::UyvyBgra,
+            2 => RecvColorFormat::BgrxBgra,
+            3 => RecvColorFormat::RgbxRgba,
+            4 => RecvColorFormat::UyvyRgba,
+            5 => RecvColorFormat::Fastest,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl StaticType for RecvColorFormat {
+    fn static_type() -> glib::Type {
+        recv_color_format_get_type()
+    }
+}
+
+impl<'a> glib::value::FromValueOptional<'a> for RecvColorFormat {
+    unsafe fn from_value_optional(value: &glib::Value) -> Option<Self> {
+        Some(glib::value::FromValue::from_value(value))
+    }
+}
+
+impl<'a> glib::value::FromValue<'a> for RecvColorFormat {
+    unsafe fn from_value(value: &glib::Value) -> Self {
+        use glib::translate::ToGlibPtr;
+
+        glib::translate::from_glib(gobject_sys::g_value_get_enum(value.to_glib_none().0))
+    }
+}
+
+impl glib::value::SetValue for RecvColorFormat {
+    unsafe fn set_value(value: &mut glib::Value, this: &Self) {
+        use glib::translate::{ToGlib, ToGlibPtrMut};
+
+        gobject_sys::g_value_set_enum(value.to_glib_none_mut().0, this.to_glib())
+    }
+}
+
+fn recv_color_format_get_type() -> glib::Type {
+    use std::sync::Once;
+    static ONCE: Once = Once::new();
+    static mut TYPE: glib::Type = glib::Type::Invalid;
+
+    ONCE.call_once(|| {
+        use std::ffi;
+        use std::ptr;
+
+        static mut VALUES: [gobject_sys::GEnumValue; 7] = [
+            gobject_sys::GEnumValue {
+                value: RecvColorFormat::Auto as i32,
+                value_name: b"Auto\0" as *const _ as *const _,
+                value_nick: b"auto\0" as *const _ as *const _,
+            },
+            gobject_sys::GEnumValue {
+                value: RecvColorFormat::UyvyBgra as i32,
+                value_name: b"UYVY or BGRA\0" as *const _ as *const _,
+                value_nick: b"uyvy-bgra\0" as *const _ as *const _,
+            },
+            gobject_sys::GEnumValue {
+                value: RecvColorFormat::BgrxBgra as i32,
+                value_name: b"BGRX or BGRA\0" as *const _ as *const _,
+                value_nick: b"bgrx-bgra\0" as *const _ as *const _,
+            },
+            gobject_sys::GEnumValue {
+                value: RecvColorFormat::RgbxRgba as i32,
+                value_name: b"RGBX or RGBA\0" as *const _ as *const _,
+                value_nick: b"rgbx-rgba\0" as *const _ as *const _,
+            },
+            gobject_sys::GEnumValue {
+                value: RecvColorFormat::UyvyRgba as i32,
+                value_name: b"UYVY or RGBA\0" as *const _ as *const _,
+                value_nick: b"uyvy-rgba\0" as *const _ as *const _,
+            },
+            gobject_sys::GEnumValue {
+                value: RecvColorFormat::Fastest as i32,
+                value_name: b"Fastest\0" as *const _ as *const _,
+                value_nick: b"fastest\0" as *const _ as *const _,
+            },
+            gobject_sys::GEnumValue {
+                value: 0,
+                value_name: ptr::null(),
+                value_nick: ptr::null(),
+            },
+        ];
+
+        let name = ffi::CString::new("GstNdiRecvColorFormat").unwrap();
+        unsafe {
+            let type_ = gobject_sys::g_enum_register_static(name.as_ptr(), VALUES.as_ptr());
+            TYPE = glib::translate::from_glib(type_);
+        }
+    });
+
+    unsafe {
+        assert_ne!(TYPE, glib::Type::Invalid);
+        TYPE
+    }
+}
+
+impl glib::translate::ToGlib for TimeoutAction {
+    type GlibType = i32;
+
+    fn to_glib(&self) -> i32 {
+        *self as i32
+    }
+}
+
+impl glib::translate::FromGlib<i32> for TimeoutAction {
+    fn from_glib(value: i32) -> Self {
+        match value {
+            0 => TimeoutAction::Eos,
+            1 => TimeoutAction::Error,
+            2 => TimeoutAction::Reconnect,
+            3 => TimeoutAction::Wait,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl StaticType for TimeoutAction {
+    fn static_type() -> glib::Type {
+        timeout_action_get_type()
+    }
+}
+
+impl<'a> glib::value::FromValueOptional<'a> for TimeoutAction {
+    unsafe fn from_value_optional(value: &glib::Value) -> Option<Self> {
+        Some(glib::value::FromValue::from_value(value))
+    }
+}
+
+impl<'a> glib::value::FromValue<'a> for TimeoutAction {
+    unsafe fn from_value(value: &glib::Value) -> Self {
+        use glib::translate::ToGlibPtr;
+
+        glib::translate::from_glib(gobject_sys::g_value_get_enum(value.to_glib_none().0))
+    }
+}
+
+impl glib::value::SetValue for TimeoutAction {
+    unsafe fn set_value(value: &mut glib::Value, this: &Self) {
+        use glib::translate::{ToGlib, ToGlibPtrMut};
+
+        gobject_sys::g_value_set_enum(value.to_glib_none_mut().0, this.to_glib())
+    }
+}
+
+fn timeout_action_get_type() -> glib::Type {
+    use std::sync::Once;
+    static ONCE: Once = Once::new();
+    static mut TYPE: glib::Type = glib::Type::Invalid;
+
+    ONCE.call_once(|| {
+        use std::ffi;
+        use std::ptr;
+
+        static mut VALUES: [gobject_sys::GEnumValue; 5] = [
+            gobject_sys::GEnumValue {
+                value: TimeoutAction::Eos as i32,
+                value_name: b"End of stream\0" as *const _ as *const _,
+                value_nick: b"eos\0" as *const _ as *const _,
+            },
+            gobject_sys::GEnumValue {
+                value: TimeoutAction::Error as i32,
+                value_name: b"Error\0" as *const _ as *const _,
+                value_nick: b"error\0" as *const _ as *const _,
+            },
+            gobject_sys::GEnumValue {
+                value: TimeoutAction::Reconnect as i32,
+                value_name: b"Reconnect\0" as *const _ as *const _,
+                value_nick: b"reconnect\0" as *const _ as *const _,
+            },
+            gobject_sys::GEnumValue {
+                value: TimeoutAction::Wait as i32,
+                value_name: b"Wait\0" as *const _ as *const _,
+                value_nick: b"wait\0" as *const _ as *const _,
+            },
+            gobject_sys::GEnumValue {
+                value: 0,
+                value_name: ptr::null(),
+                value_nick: ptr::null(),
+            },
+        ];
+
+        let name = ffi::CString::new("GstNdiTimeoutAction").unwrap();
+        unsafe {
+            let type_ = gobject_sys::g_enum_register_static(name.as_ptr(), VALUES.as_ptr());
+            TYPE = glib::translate::from_glib(type_);
+        }
+    });
+
+    unsafe {
+        assert_ne!(TYPE, glib::Type::Invalid);
+        TYPE
+    }
+}
+
+impl glib::translate::ToGlib for SendClockMode {
+    type GlibType = i32;
+
+    fn to_glib(&self) -> i32 {
+        *self as i32
+    }
+}
+
+impl glib::translate::FromGlib<i32> for SendClockMode {
+    fn from_glib(value: i32) -> Self {
+        match value {
+            0 => SendClockMode::Auto,
+            1 => SendClockMode::Video,
+            2 => SendClockMode::Audio,
+            3 => SendClockMode::Disabled,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl StaticType for SendClockMode {
+    fn static_type() -> glib::Type {
+        send_clock_mode_get_type()
+    }
+}
+
+impl<'a> glib::value::FromValueOptional<'a> for SendClockMode {
+    unsafe fn from_value_optional(value: &glib::Value) -> Option<Self> {
+        Some(glib::value::FromValue::from_value(value))
+    }
+}
+
+impl<'a> glib::value::FromValue<'a> for SendClockMode {
+    unsafe fn from_value(value: &glib::Value) -> Self {
+        use glib::translate::ToGlibPtr;
+
+        glib::translate::from_glib(gobject_sys::g_value_get_enum(value.to_glib_none().0))
+    }
+}
+
+impl glib::value::SetValue for SendClockMode {
+    unsafe fn set_value(value: &mut glib::Value, this: &Self) {
+        use glib::translate::{ToGlib, ToGlibPtrMut};
+
+        gobject_sys::g_value_set_enum(value.to_glib_none_mut().0, this.to_glib())
+    }
+}
+
+fn send_clock_mode_get_type() -> glib::Type {
+    use std::sync::Once;
+    static ONCE: Once = Once::new();
+    static mut TYPE: glib::Type = glib::Type::Invalid;
+
+    ONCE.call_once(|| {
+        use std::ffi;
+        use std::ptr;
+
+        static mut VALUES: [gobject_sys::GEnumValue; 5] = [
+            gobject_sys::GEnumValue {
+                value: SendClockMode::Auto as i32,
+                value_name: b"Video if available, otherwise audio\0" as *const _ as *const _,
+                value_nick: b"auto\0" as *const _ as *const _,
+            },
+            gobject_sys::GEnumValue {
+                value: SendClockMode::Video as i32,
+                value_name: b"Video\0" as *const _ as *const _,
+                value_nick: b"video\0" as *const _ as *const _,
+            },
+            gobject_sys::GEnumValue {
+                value: SendClockMode::Audio as i32,
+                value_name: b"Audio\0" as *const _ as *const _,
+                value_nick: b"audio\0" as *const _ as *const _,
+            },
+            gobject_sys::GEnumValue {
+                value: SendClockMode::Disabled as i32,
+                value_name: b"Disabled\0" as *const _ as *const _,
+                value_nick: b"disabled\0" as *const _ as *const _,
+            },
+            gobject_sys::GEnumValue {
+                value: 0,
+                value_name: ptr::null(),
+                value_nick: ptr::null(),
+            },
+        ];
+
+        let name = ffi::CString::new("GstNdiSendClockMode").unwrap();
+        unsafe {
+            let type_ = gobject_sys::g_enum_register_static(name.as_ptr(), VALUES.as_ptr());
+            TYPE = glib::translate::from_glib(type_);
+        }
+    });
+
+    unsafe {
+        assert_ne!(TYPE, glib::Type::Invalid);
+        TYPE
+    }
+}
+
 gst_plugin_define!(
     ndi,
     env!("CARGO_PKG_DESCRIPTION"),
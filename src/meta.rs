@@ -0,0 +1,169 @@
+use glib;
+use glib::translate::{from_glib, ToGlib};
+use gst;
+use gst::MetaAPI;
+use gst_sys;
+
+use std::borrow::Cow;
+use std::ffi::CStr;
+use std::fmt;
+use std::mem;
+use std::os::raw::c_char;
+use std::ptr;
+
+/// Per-frame NDI metadata XML attached to a buffer
+///
+/// The sources attach it to buffers created from frames that carried metadata, and the
+/// sinks send it as the `p_metadata` of the frame created from the buffer.
+///
+/// Other languages can look it up via the `GstNdiFrameMetadataMetaAPI` meta API type. The
+/// meta is laid out as `struct { GstMeta meta; gchar *metadata; }`, where `metadata` is a
+/// NUL-terminated UTF-8 string owned by the meta and allocated with `g_malloc()`.
+#[repr(C)]
+pub struct NdiFrameMetadataMeta(imp::NdiFrameMetadataMeta);
+
+unsafe impl Send for NdiFrameMetadataMeta {}
+unsafe impl Sync for NdiFrameMetadataMeta {}
+
+impl NdiFrameMetadataMeta {
+    /// Adds the metadata up to the first NUL byte, if any
+    pub fn add(
+        buffer: &mut gst::BufferRef,
+        metadata: &str,
+    ) -> gst::MetaRefMut<Self, gst::meta::Standalone> {
+        unsafe {
+            let metadata = glib_sys::g_strndup(metadata.as_ptr() as *const c_char, metadata.len());
+            let meta = imp::ndi_frame_metadata_meta_add(buffer.as_mut_ptr(), metadata);
+
+            Self::from_mut_ptr(buffer, meta)
+        }
+    }
+
+    pub fn get_metadata(&self) -> Cow<str> {
+        unsafe { CStr::from_ptr(self.0.metadata).to_string_lossy() }
+    }
+}
+
+unsafe impl MetaAPI for NdiFrameMetadataMeta {
+    type GstType = imp::NdiFrameMetadataMeta;
+
+    fn get_meta_api() -> glib::Type {
+        imp::ndi_frame_metadata_meta_api_get_type()
+    }
+}
+
+impl fmt::Debug for NdiFrameMetadataMeta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NdiFrameMetadataMeta")
+            .field("metadata", &self.get_metadata())
+            .finish()
+    }
+}
+
+mod imp {
+    use super::*;
+
+    struct NdiFrameMetadataMetaParams {
+        metadata: *mut c_char,
+    }
+
+    #[repr(C)]
+    pub struct NdiFrameMetadataMeta {
+        parent: gst_sys::GstMeta,
+        pub(super) metadata: *mut c_char,
+    }
+
+    // Takes ownership of `metadata`, which must be allocated with `g_malloc()`
+    pub(super) unsafe fn ndi_frame_metadata_meta_add(
+        buffer: *mut gst_sys::GstBuffer,
+        metadata: *mut c_char,
+    ) -> *mut NdiFrameMetadataMeta {
+        let mut params = NdiFrameMetadataMetaParams { metadata };
+
+        gst_sys::gst_buffer_add_meta(
+            buffer,
+            ndi_frame_metadata_meta_get_info(),
+            &mut params as *mut NdiFrameMetadataMetaParams as glib_sys::gpointer,
+        ) as *mut NdiFrameMetadataMeta
+    }
+
+    pub(super) fn ndi_frame_metadata_meta_api_get_type() -> glib::Type {
+        lazy_static! {
+            static ref TYPE: glib::Type = unsafe {
+                let t = from_glib(gst_sys::gst_meta_api_type_register(
+                    b"GstNdiFrameMetadataMetaAPI\0".as_ptr() as *const _,
+                    [ptr::null::<std::os::raw::c_char>()].as_ptr() as *mut *const _,
+                ));
+
+                assert_ne!(t, glib::Type::Invalid);
+
+                t
+            };
+        }
+
+        *TYPE
+    }
+
+    unsafe extern "C" fn ndi_frame_metadata_meta_init(
+        meta: *mut gst_sys::GstMeta,
+        params: glib_sys::gpointer,
+        _buffer: *mut gst_sys::GstBuffer,
+    ) -> glib_sys::gboolean {
+        assert!(!params.is_null());
+
+        let meta = &mut *(meta as *mut NdiFrameMetadataMeta);
+        let params = &*(params as *const NdiFrameMetadataMetaParams);
+
+        meta.metadata = params.metadata;
+
+        glib_sys::GTRUE
+    }
+
+    unsafe extern "C" fn ndi_frame_metadata_meta_free(
+        meta: *mut gst_sys::GstMeta,
+        _buffer: *mut gst_sys::GstBuffer,
+    ) {
+        let meta = &mut *(meta as *mut NdiFrameMetadataMeta);
+
+        glib_sys::g_free(meta.metadata as glib_sys::gpointer);
+        meta.metadata = ptr::null_mut();
+    }
+
+    unsafe extern "C" fn ndi_frame_metadata_meta_transform(
+        dest: *mut gst_sys::GstBuffer,
+        meta: *mut gst_sys::GstMeta,
+        _buffer: *mut gst_sys::GstBuffer,
+        _type_: glib_sys::GQuark,
+        _data: glib_sys::gpointer,
+    ) -> glib_sys::gboolean {
+        let meta = &*(meta as *mut NdiFrameMetadataMeta);
+
+        ndi_frame_metadata_meta_add(dest, glib_sys::g_strdup(meta.metadata));
+
+        glib_sys::GTRUE
+    }
+
+    pub(super) fn ndi_frame_metadata_meta_get_info() -> *const gst_sys::GstMetaInfo {
+        struct MetaInfo(ptr::NonNull<gst_sys::GstMetaInfo>);
+        unsafe impl Send for MetaInfo {}
+        unsafe impl Sync for MetaInfo {}
+
+        lazy_static! {
+            static ref META_INFO: MetaInfo = unsafe {
+                MetaInfo(
+                    ptr::NonNull::new(gst_sys::gst_meta_register(
+                        ndi_frame_metadata_meta_api_get_type().to_glib(),
+                        b"GstNdiFrameMetadataMeta\0".as_ptr() as *const _,
+                        mem::size_of::<NdiFrameMetadataMeta>(),
+                        Some(ndi_frame_metadata_meta_init),
+                        Some(ndi_frame_metadata_meta_free),
+                        Some(ndi_frame_metadata_meta_transform),
+                    ) as *mut gst_sys::GstMetaInfo)
+                    .expect("Failed to register meta API"),
+                )
+            };
+        }
+
+        META_INFO.0.as_ptr()
+    }
+}
@@ -267,6 +267,11 @@ impl RecvInstance {
         }
     }
 
+    /// Whether both refer to the same connection
+    pub fn ptr_eq(&self, other: &RecvInstance) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
     pub fn set_tally(&self, tally: &Tally) -> bool {
         unsafe {
             let _lock = (self.0).1.lock().unwrap();
@@ -281,6 +286,137 @@ impl RecvInstance {
         }
     }
 
+    /// Whether the sender is a PTZ camera that can be controlled with the `ptz_*` functions
+    pub fn ptz_is_supported(&self) -> bool {
+        unsafe {
+            let _lock = (self.0).1.lock().unwrap();
+            NDIlib_recv_ptz_is_supported(((self.0).0).0.as_ptr())
+        }
+    }
+
+    /// Stores the current position, focus, etc. as preset 0 to 99
+    pub fn ptz_store_preset(&self, preset_no: i32) -> bool {
+        unsafe {
+            let _lock = (self.0).1.lock().unwrap();
+            NDIlib_recv_ptz_store_preset(((self.0).0).0.as_ptr(), preset_no)
+        }
+    }
+
+    /// Recalls a stored preset, moving to it with a speed between 0.0 and 1.0
+    pub fn ptz_recall_preset(&self, preset_no: i32, speed: f32) -> bool {
+        unsafe {
+            let _lock = (self.0).1.lock().unwrap();
+            NDIlib_recv_ptz_recall_preset(((self.0).0).0.as_ptr(), preset_no, speed)
+        }
+    }
+
+    /// Zooms to an absolute value between 0.0 (zoomed in) and 1.0 (zoomed out)
+    pub fn ptz_zoom(&self, zoom_value: f32) -> bool {
+        unsafe {
+            let _lock = (self.0).1.lock().unwrap();
+            NDIlib_recv_ptz_zoom(((self.0).0).0.as_ptr(), zoom_value)
+        }
+    }
+
+    /// Zooms with a speed between -1.0 (zoom outwards) and 1.0 (zoom inwards)
+    pub fn ptz_zoom_speed(&self, zoom_speed: f32) -> bool {
+        unsafe {
+            let _lock = (self.0).1.lock().unwrap();
+            NDIlib_recv_ptz_zoom_speed(((self.0).0).0.as_ptr(), zoom_speed)
+        }
+    }
+
+    /// Moves to an absolute pan and tilt between -1.0 and 1.0 each
+    pub fn ptz_pan_tilt(&self, pan_value: f32, tilt_value: f32) -> bool {
+        unsafe {
+            let _lock = (self.0).1.lock().unwrap();
+            NDIlib_recv_ptz_pan_tilt(((self.0).0).0.as_ptr(), pan_value, tilt_value)
+        }
+    }
+
+    /// Pans and tilts with speeds between -1.0 and 1.0 each
+    pub fn ptz_pan_tilt_speed(&self, pan_speed: f32, tilt_speed: f32) -> bool {
+        unsafe {
+            let _lock = (self.0).1.lock().unwrap();
+            NDIlib_recv_ptz_pan_tilt_speed(((self.0).0).0.as_ptr(), pan_speed, tilt_speed)
+        }
+    }
+
+    pub fn ptz_auto_focus(&self) -> bool {
+        unsafe {
+            let _lock = (self.0).1.lock().unwrap();
+            NDIlib_recv_ptz_auto_focus(((self.0).0).0.as_ptr())
+        }
+    }
+
+    /// Focuses to an absolute value between 0.0 (focused to infinity) and 1.0 (focused as
+    /// close as possible)
+    pub fn ptz_focus(&self, focus_value: f32) -> bool {
+        unsafe {
+            let _lock = (self.0).1.lock().unwrap();
+            NDIlib_recv_ptz_focus(((self.0).0).0.as_ptr(), focus_value)
+        }
+    }
+
+    /// Focuses with a speed between -1.0 (focus outwards) and 1.0 (focus inwards)
+    pub fn ptz_focus_speed(&self, focus_speed: f32) -> bool {
+        unsafe {
+            let _lock = (self.0).1.lock().unwrap();
+            NDIlib_recv_ptz_focus_speed(((self.0).0).0.as_ptr(), focus_speed)
+        }
+    }
+
+    pub fn ptz_white_balance_auto(&self) -> bool {
+        unsafe {
+            let _lock = (self.0).1.lock().unwrap();
+            NDIlib_recv_ptz_white_balance_auto(((self.0).0).0.as_ptr())
+        }
+    }
+
+    pub fn ptz_white_balance_indoor(&self) -> bool {
+        unsafe {
+            let _lock = (self.0).1.lock().unwrap();
+            NDIlib_recv_ptz_white_balance_indoor(((self.0).0).0.as_ptr())
+        }
+    }
+
+    pub fn ptz_white_balance_outdoor(&self) -> bool {
+        unsafe {
+            let _lock = (self.0).1.lock().unwrap();
+            NDIlib_recv_ptz_white_balance_outdoor(((self.0).0).0.as_ptr())
+        }
+    }
+
+    pub fn ptz_white_balance_oneshot(&self) -> bool {
+        unsafe {
+            let _lock = (self.0).1.lock().unwrap();
+            NDIlib_recv_ptz_white_balance_oneshot(((self.0).0).0.as_ptr())
+        }
+    }
+
+    /// Sets the red and blue white balance between 0.0 and 1.0 each
+    pub fn ptz_white_balance_manual(&self, red: f32, blue: f32) -> bool {
+        unsafe {
+            let _lock = (self.0).1.lock().unwrap();
+            NDIlib_recv_ptz_white_balance_manual(((self.0).0).0.as_ptr(), red, blue)
+        }
+    }
+
+    pub fn ptz_exposure_auto(&self) -> bool {
+        unsafe {
+            let _lock = (self.0).1.lock().unwrap();
+            NDIlib_recv_ptz_exposure_auto(((self.0).0).0.as_ptr())
+        }
+    }
+
+    /// Sets the exposure between 0.0 (dark) and 1.0 (light)
+    pub fn ptz_exposure_manual(&self, exposure_level: f32) -> bool {
+        unsafe {
+            let _lock = (self.0).1.lock().unwrap();
+            NDIlib_recv_ptz_exposure_manual(((self.0).0).0.as_ptr(), exposure_level)
+        }
+    }
+
     pub fn get_queue(&self) -> Queue {
         unsafe {
             let _lock = (self.0).1.lock().unwrap();
@@ -441,25 +577,8 @@ impl<'a> VideoFrame<'a> {
     }
 
     pub fn data(&self) -> &[u8] {
-        // FIXME: Unclear if this is correct. Needs to be validated against an actual
-        // interlaced stream
-        let frame_size = if self.frame_format_type()
-            == NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_0
-            || self.frame_format_type()
-                == NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_1
-        {
-            self.yres() * self.line_stride_in_bytes() / 2
-        } else {
-            self.yres() * self.line_stride_in_bytes()
-        };
-
-        unsafe {
-            use std::slice;
-            match self {
-                VideoFrame::Borrowed(ref frame, _) => {
-                    slice::from_raw_parts(frame.p_data as *const u8, frame_size as usize)
-                }
-            }
+        match self {
+            VideoFrame::Borrowed(ref frame, _) => video_frame_data(frame),
         }
     }
 
@@ -494,6 +613,17 @@ impl<'a> VideoFrame<'a> {
             VideoFrame::Borrowed(ref frame, _) => frame,
         }
     }
+
+    /// Takes ownership of the frame so that its memory can be wrapped in a buffer without
+    /// copying. The frame is given back to the SDK once the returned value is dropped
+    pub fn into_memory(self) -> VideoFrameMemory {
+        let (frame, recv) = match self {
+            VideoFrame::Borrowed(ref frame, recv) => (*frame, recv.clone()),
+        };
+        mem::forget(self);
+
+        VideoFrameMemory(frame, recv)
+    }
 }
 
 impl<'a> Drop for VideoFrame<'a> {
@@ -507,6 +637,52 @@ impl<'a> Drop for VideoFrame<'a> {
     }
 }
 
+fn video_frame_data(frame: &NDIlib_video_frame_v2_t) -> &[u8] {
+    let yres = frame.yres as usize;
+    let stride = frame.line_stride_in_bytes as usize;
+
+    let mut frame_size = match frame.FourCC {
+        // UYVA has a full resolution alpha plane with a stride of xres after the UYVY plane
+        NDIlib_FourCC_type_e::NDIlib_FourCC_type_UYVA => yres * stride + yres * frame.xres as usize,
+        // Interleaved UV plane with the same stride as the Y plane
+        NDIlib_FourCC_type_e::NDIlib_FourCC_type_NV12 => yres * stride + (yres + 1) / 2 * stride,
+        // Two chroma planes with half the stride of the Y plane
+        NDIlib_FourCC_type_e::NDIlib_FourCC_type_YV12
+        | NDIlib_FourCC_type_e::NDIlib_FourCC_type_I420 => {
+            yres * stride + 2 * ((yres + 1) / 2 * (stride / 2))
+        }
+        _ => yres * stride,
+    };
+
+    // FIXME: Unclear if this is correct. Needs to be validated against an actual
+    // interlaced stream
+    if frame.frame_format_type == NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_0
+        || frame.frame_format_type == NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_1
+    {
+        frame_size /= 2;
+    }
+
+    unsafe { std::slice::from_raw_parts(frame.p_data as *const u8, frame_size) }
+}
+
+#[derive(Debug)]
+pub struct VideoFrameMemory(NDIlib_video_frame_v2_t, RecvInstance);
+unsafe impl Send for VideoFrameMemory {}
+
+impl AsRef<[u8]> for VideoFrameMemory {
+    fn as_ref(&self) -> &[u8] {
+        video_frame_data(&self.0)
+    }
+}
+
+impl Drop for VideoFrameMemory {
+    fn drop(&mut self) {
+        unsafe {
+            NDIlib_recv_free_video_v2((((self.1).0).0).0.as_ptr() as *mut _, &mut self.0);
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum AudioFrame<'a> {
     //Owned(NDIlib_audio_frame_v2_t, Option<ffi::CString>, Option<Vec<u8>>),
@@ -582,7 +758,7 @@ impl<'a> AudioFrame<'a> {
         }
     }
 
-    pub fn copy_to_interleaved_16s(&self, data: &mut [i16]) {
+    pub fn copy_to_interleaved_16s(&self, data: &mut [i16], reference_level: i32) {
         assert_eq!(
             data.len(),
             (self.no_samples() * self.no_channels()) as usize
@@ -593,7 +769,7 @@ impl<'a> AudioFrame<'a> {
             no_channels: self.no_channels(),
             no_samples: self.no_samples(),
             timecode: self.timecode(),
-            reference_level: 0,
+            reference_level,
             p_data: data.as_mut_ptr(),
         };
 
@@ -601,6 +777,51 @@ impl<'a> AudioFrame<'a> {
             NDIlib_util_audio_to_interleaved_16s_v2(self.as_ptr(), &mut dst);
         }
     }
+
+    pub fn copy_to_interleaved_32f(&self, data: &mut [f32]) {
+        assert_eq!(
+            data.len(),
+            (self.no_samples() * self.no_channels()) as usize
+        );
+
+        let mut dst = NDIlib_audio_frame_interleaved_32f_t {
+            sample_rate: self.sample_rate(),
+            no_channels: self.no_channels(),
+            no_samples: self.no_samples(),
+            timecode: self.timecode(),
+            p_data: data.as_mut_ptr(),
+        };
+
+        unsafe {
+            NDIlib_util_audio_to_interleaved_32f_v2(self.as_ptr(), &mut dst);
+        }
+    }
+
+    pub fn copy_to_planar_32f(&self, data: &mut [f32]) {
+        assert_eq!(
+            data.len(),
+            (self.no_samples() * self.no_channels()) as usize
+        );
+
+        let no_samples = self.no_samples() as usize;
+        let channel_stride = self.channel_stride_in_bytes() as usize;
+
+        for (channel, dest) in data.chunks_exact_mut(no_samples).enumerate() {
+            unsafe {
+                use std::slice;
+                match self {
+                    AudioFrame::Borrowed(ref frame, _) => {
+                        let src = slice::from_raw_parts(
+                            (frame.p_data as *const u8).add(channel * channel_stride)
+                                as *const f32,
+                            no_samples,
+                        );
+                        dest.copy_from_slice(src);
+                    }
+                }
+            }
+        }
+    }
 }
 
 impl<'a> Drop for AudioFrame<'a> {
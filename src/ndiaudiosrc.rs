@@ -18,6 +18,7 @@ use crate::AudioReceiver;
 use crate::Receiver;
 use crate::ReceiverControlHandle;
 use crate::ReceiverItem;
+use crate::TimeoutAction;
 use crate::TimestampMode;
 use crate::DEFAULT_RECEIVER_NDI_NAME;
 
@@ -25,11 +26,18 @@ use crate::DEFAULT_RECEIVER_NDI_NAME;
 struct Settings {
     ndi_name: Option<String>,
     ip_address: Option<String>,
+    groups: Option<String>,
+    extra_ips: Option<String>,
+    show_local_sources: bool,
     connect_timeout: u32,
     timeout: u32,
+    timeout_action: TimeoutAction,
     receiver_ndi_name: String,
     bandwidth: ndisys::NDIlib_recv_bandwidth_e,
     timestamp_mode: TimestampMode,
+    on_program: bool,
+    on_preview: bool,
+    reference_level: i32,
 }
 
 impl Default for Settings {
@@ -37,16 +45,23 @@ impl Default for Settings {
         Settings {
             ndi_name: None,
             ip_address: None,
+            groups: None,
+            extra_ips: None,
+            show_local_sources: true,
             receiver_ndi_name: DEFAULT_RECEIVER_NDI_NAME.clone(),
             connect_timeout: 10000,
             timeout: 5000,
+            timeout_action: TimeoutAction::Eos,
             bandwidth: ndisys::NDIlib_recv_bandwidth_highest,
             timestamp_mode: TimestampMode::ReceiveTime,
+            on_program: false,
+            on_preview: false,
+            reference_level: 0,
         }
     }
 }
 
-static PROPERTIES: [subclass::Property; 7] = [
+static PROPERTIES: [subclass::Property; 14] = [
     subclass::Property("ndi-name", |name| {
         glib::ParamSpec::string(
             name,
@@ -65,6 +80,33 @@ static PROPERTIES: [subclass::Property; 7] = [
             glib::ParamFlags::READWRITE,
         )
     }),
+    subclass::Property("groups", |name| {
+        glib::ParamSpec::string(
+            name,
+            "Groups",
+            "Comma separated list of NDI groups to search for the sender in",
+            None,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("extra-ips", |name| {
+        glib::ParamSpec::string(
+            name,
+            "Extra IPs",
+            "Comma separated list of additional IP addresses to search for the sender on",
+            None,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("show-local-sources", |name| {
+        glib::ParamSpec::boolean(
+            name,
+            "Show Local Sources",
+            "Whether to consider senders running on this machine",
+            true,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
     subclass::Property("receiver-ndi-name", |name| {
         glib::ParamSpec::string(
             name,
@@ -96,6 +138,16 @@ static PROPERTIES: [subclass::Property; 7] = [
             glib::ParamFlags::READWRITE,
         )
     }),
+    subclass::Property("timeout-action", |name| {
+        glib::ParamSpec::enum_(
+            name,
+            "Timeout Action",
+            "What to do if no frames are received for the receive timeout",
+            TimeoutAction::static_type(),
+            TimeoutAction::Eos as i32,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
     subclass::Property("bandwidth", |name| {
         glib::ParamSpec::int(
             name,
@@ -117,6 +169,35 @@ static PROPERTIES: [subclass::Property; 7] = [
             glib::ParamFlags::READWRITE,
         )
     }),
+    subclass::Property("on-program", |name| {
+        glib::ParamSpec::boolean(
+            name,
+            "On Program",
+            "Tell the sender that this source is on program",
+            false,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("on-preview", |name| {
+        glib::ParamSpec::boolean(
+            name,
+            "On Preview",
+            "Tell the sender that this source is on preview",
+            false,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("reference-level", |name| {
+        glib::ParamSpec::int(
+            name,
+            "Reference Level",
+            "Audio reference level in dB used when converting to S16",
+            0,
+            20,
+            0,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
 ];
 
 struct State {
@@ -171,19 +252,39 @@ impl ObjectSubclass for NdiAudioSrc {
             "Ruben Gonzalez <rubenrua@teltek.es>, Daniel Vilar <daniel.peiteado@teltek.es>, Sebastian Dröge <sebastian@centricular.com>",
         );
 
+        // Non-interleaved buffers can only be described with an audio meta
+        #[cfg(feature = "audio-meta")]
+        let layouts = gst::List::new(&[&"interleaved", &"non-interleaved"]);
+        #[cfg(not(feature = "audio-meta"))]
+        let layouts = gst::List::new(&[&"interleaved"]);
+
         let caps = gst::Caps::new_simple(
             "audio/x-raw",
             &[
-                (
-                    "format",
-                    &gst::List::new(&[&gst_audio::AUDIO_FORMAT_S16.to_string()]),
-                ),
+                ("format", &gst_audio::AUDIO_FORMAT_F32.to_string()),
                 ("rate", &gst::IntRange::<i32>::new(1, i32::MAX)),
                 ("channels", &gst::IntRange::<i32>::new(1, i32::MAX)),
-                ("layout", &"interleaved"),
+                ("layout", &layouts),
             ],
         );
 
+        let caps = {
+            let mut caps = caps;
+            {
+                let caps = caps.get_mut().unwrap();
+                caps.append(gst::Caps::new_simple(
+                    "audio/x-raw",
+                    &[
+                        ("format", &gst_audio::AUDIO_FORMAT_S16.to_string()),
+                        ("rate", &gst::IntRange::<i32>::new(1, i32::MAX)),
+                        ("channels", &gst::IntRange::<i32>::new(1, i32::MAX)),
+                        ("layout", &"interleaved"),
+                    ],
+                ));
+            }
+            caps
+        };
+
         let src_pad_template = gst::PadTemplate::new(
             "src",
             gst::PadDirection::Src,
@@ -239,6 +340,42 @@ impl ObjectImpl for NdiAudioSrc {
                 );
                 settings.ip_address = ip_address;
             }
+            subclass::Property("groups", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let groups = value.get();
+                gst_debug!(
+                    self.cat,
+                    obj: basesrc,
+                    "Changing groups from {:?} to {:?}",
+                    settings.groups,
+                    groups,
+                );
+                settings.groups = groups;
+            }
+            subclass::Property("extra-ips", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let extra_ips = value.get();
+                gst_debug!(
+                    self.cat,
+                    obj: basesrc,
+                    "Changing extra-ips from {:?} to {:?}",
+                    settings.extra_ips,
+                    extra_ips,
+                );
+                settings.extra_ips = extra_ips;
+            }
+            subclass::Property("show-local-sources", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let show_local_sources = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: basesrc,
+                    "Changing show-local-sources from {} to {}",
+                    settings.show_local_sources,
+                    show_local_sources,
+                );
+                settings.show_local_sources = show_local_sources;
+            }
             subclass::Property("receiver-ndi-name", ..) => {
                 let mut settings = self.settings.lock().unwrap();
                 let receiver_ndi_name = value.get();
@@ -276,6 +413,18 @@ impl ObjectImpl for NdiAudioSrc {
                 );
                 settings.timeout = timeout;
             }
+            subclass::Property("timeout-action", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let timeout_action = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: basesrc,
+                    "Changing timeout action from {:?} to {:?}",
+                    settings.timeout_action,
+                    timeout_action,
+                );
+                settings.timeout_action = timeout_action;
+            }
             subclass::Property("bandwidth", ..) => {
                 let mut settings = self.settings.lock().unwrap();
                 let bandwidth = value.get().unwrap();
@@ -304,6 +453,56 @@ impl ObjectImpl for NdiAudioSrc {
                 }
                 settings.timestamp_mode = timestamp_mode;
             }
+            subclass::Property("on-program", ..) => {
+                let (on_program, on_preview) = {
+                    let mut settings = self.settings.lock().unwrap();
+                    let on_program = value.get().unwrap();
+                    gst_debug!(
+                        self.cat,
+                        obj: basesrc,
+                        "Changing on-program from {} to {}",
+                        settings.on_program,
+                        on_program
+                    );
+                    settings.on_program = on_program;
+                    (settings.on_program, settings.on_preview)
+                };
+
+                if let Some(ref controller) = *self.receiver_controller.lock().unwrap() {
+                    controller.set_tally(on_program, on_preview);
+                }
+            }
+            subclass::Property("on-preview", ..) => {
+                let (on_program, on_preview) = {
+                    let mut settings = self.settings.lock().unwrap();
+                    let on_preview = value.get().unwrap();
+                    gst_debug!(
+                        self.cat,
+                        obj: basesrc,
+                        "Changing on-preview from {} to {}",
+                        settings.on_preview,
+                        on_preview
+                    );
+                    settings.on_preview = on_preview;
+                    (settings.on_program, settings.on_preview)
+                };
+
+                if let Some(ref controller) = *self.receiver_controller.lock().unwrap() {
+                    controller.set_tally(on_program, on_preview);
+                }
+            }
+            subclass::Property("reference-level", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let reference_level = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: basesrc,
+                    "Changing reference level from {} to {}",
+                    settings.reference_level,
+                    reference_level,
+                );
+                settings.reference_level = reference_level;
+            }
             _ => unimplemented!(),
         }
     }
@@ -320,6 +519,18 @@ impl ObjectImpl for NdiAudioSrc {
                 let settings = self.settings.lock().unwrap();
                 Ok(settings.ip_address.to_value())
             }
+            subclass::Property("groups", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.groups.to_value())
+            }
+            subclass::Property("extra-ips", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.extra_ips.to_value())
+            }
+            subclass::Property("show-local-sources", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.show_local_sources.to_value())
+            }
             subclass::Property("receiver-ndi-name", ..) => {
                 let settings = self.settings.lock().unwrap();
                 Ok(settings.receiver_ndi_name.to_value())
@@ -332,6 +543,10 @@ impl ObjectImpl for NdiAudioSrc {
                 let settings = self.settings.lock().unwrap();
                 Ok(settings.timeout.to_value())
             }
+            subclass::Property("timeout-action", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.timeout_action.to_value())
+            }
             subclass::Property("bandwidth", ..) => {
                 let settings = self.settings.lock().unwrap();
                 Ok(settings.bandwidth.to_value())
@@ -340,6 +555,18 @@ impl ObjectImpl for NdiAudioSrc {
                 let settings = self.settings.lock().unwrap();
                 Ok(settings.timestamp_mode.to_value())
             }
+            subclass::Property("on-program", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.on_program.to_value())
+            }
+            subclass::Property("on-preview", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.on_preview.to_value())
+            }
+            subclass::Property("reference-level", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.reference_level.to_value())
+            }
             _ => unimplemented!(),
         }
     }
@@ -375,9 +602,57 @@ impl ElementImpl for NdiAudioSrc {
 }
 
 impl BaseSrcImpl for NdiAudioSrc {
-    fn negotiate(&self, _element: &gst_base::BaseSrc) -> Result<(), gst::LoggableError> {
-        // Always succeed here without doing anything: we will set the caps once we received a
-        // buffer, there's nothing we can negotiate
+    fn negotiate(&self, element: &gst_base::BaseSrc) -> Result<(), gst::LoggableError> {
+        // The actual caps are only set once we received a buffer, but the sample format and
+        // layout have to be decided now so the receiver knows what to convert to
+        let peer_caps = element.get_src_pad().get_allowed_caps();
+
+        let mut formats = vec![(
+            gst_audio::AUDIO_FORMAT_F32,
+            gst_audio::AudioLayout::Interleaved,
+            "interleaved",
+        )];
+        #[cfg(feature = "audio-meta")]
+        formats.push((
+            gst_audio::AUDIO_FORMAT_F32,
+            gst_audio::AudioLayout::NonInterleaved,
+            "non-interleaved",
+        ));
+        formats.push((
+            gst_audio::AUDIO_FORMAT_S16,
+            gst_audio::AudioLayout::Interleaved,
+            "interleaved",
+        ));
+
+        let (format, layout) = match peer_caps {
+            None => (formats[0].0, formats[0].1),
+            Some(peer_caps) => formats
+                .iter()
+                .find(|(format, _, layout_str)| {
+                    let caps = gst::Caps::new_simple(
+                        "audio/x-raw",
+                        &[("format", &format.to_string()), ("layout", layout_str)],
+                    );
+                    peer_caps.can_intersect(&caps)
+                })
+                .map(|(format, layout, _)| (*format, *layout))
+                .ok_or_else(|| gst_loggable_error!(self.cat, "No supported audio format"))?,
+        };
+
+        gst_debug!(
+            self.cat,
+            obj: element,
+            "Negotiated format {} with layout {:?}",
+            format,
+            layout
+        );
+
+        // The receiver itself is taken out of the state while producing a buffer, which is
+        // exactly when renegotiation happens, so go through the controller instead
+        if let Some(ref controller) = *self.receiver_controller.lock().unwrap() {
+            controller.set_output_format(format, layout);
+        }
+
         Ok(())
     }
 
@@ -414,10 +689,16 @@ impl BaseSrcImpl for NdiAudioSrc {
             settings.ip_address.as_ref().map(String::as_str),
             settings.ndi_name.as_ref().map(String::as_str),
             &settings.receiver_ndi_name,
+            settings.groups.as_ref().map(String::as_str),
+            settings.extra_ips.as_ref().map(String::as_str),
+            settings.show_local_sources,
             settings.connect_timeout,
             settings.bandwidth,
+            ndisys::NDIlib_recv_color_format_e::NDIlib_recv_color_format_UYVY_BGRA,
             settings.timestamp_mode,
             settings.timeout,
+            settings.timeout_action,
+            settings.reference_level,
         );
 
         // settings.id_receiver exists
@@ -427,8 +708,16 @@ impl BaseSrcImpl for NdiAudioSrc {
                 ["Could not connect to this source"]
             )),
             Some(receiver) => {
-                *self.receiver_controller.lock().unwrap() =
-                    Some(receiver.receiver_control_handle());
+                // Take the tally from the current settings while holding the controller lock
+                // so that no change from the properties is missed
+                let mut receiver_controller = self.receiver_controller.lock().unwrap();
+                let controller = receiver.receiver_control_handle();
+                {
+                    let settings = self.settings.lock().unwrap();
+                    controller.set_tally(settings.on_program, settings.on_preview);
+                }
+                *receiver_controller = Some(controller);
+                drop(receiver_controller);
                 let mut state = self.state.lock().unwrap();
                 state.receiver = Some(receiver);
 
@@ -513,45 +802,69 @@ impl BaseSrcImpl for NdiAudioSrc {
             }
         };
 
-        match recv.capture() {
-            ReceiverItem::Buffer(buffer, info) => {
-                let mut state = self.state.lock().unwrap();
-                state.receiver = Some(recv);
-                if state.info.as_ref() != Some(&info) {
-                    let caps = info.to_caps().ok_or_else(|| {
-                        gst_element_error!(
-                            element,
-                            gst::ResourceError::Settings,
-                            ["Invalid audio info received: {:?}", info]
-                        );
-                        gst::FlowError::NotNegotiated
-                    })?;
-                    state.info = Some(info.clone());
-                    state.current_latency = buffer.get_duration();
-                    drop(state);
-                    gst_debug!(self.cat, obj: element, "Configuring for caps {}", caps);
-                    element.set_caps(&caps).map_err(|_| {
-                        gst_element_error!(
-                            element,
-                            gst::CoreError::Negotiation,
-                            ["Failed to negotiate caps: {:?}", caps]
-                        );
-                        gst::FlowError::NotNegotiated
-                    })?;
-
-                    let _ = element
-                        .post_message(&gst::Message::new_latency().src(Some(element)).build());
+        loop {
+            match recv.capture() {
+                ReceiverItem::Buffer(buffer, info) => {
+                    let mut state = self.state.lock().unwrap();
+                    state.receiver = Some(recv);
+                    if state.info.as_ref() != Some(&info) {
+                        let caps = info.to_caps().ok_or_else(|| {
+                            gst_element_error!(
+                                element,
+                                gst::ResourceError::Settings,
+                                ["Invalid audio info received: {:?}", info]
+                            );
+                            gst::FlowError::NotNegotiated
+                        })?;
+                        state.info = Some(info.clone());
+                        state.current_latency = buffer.get_duration();
+                        drop(state);
+                        gst_debug!(self.cat, obj: element, "Configuring for caps {}", caps);
+                        element.set_caps(&caps).map_err(|_| {
+                            gst_element_error!(
+                                element,
+                                gst::CoreError::Negotiation,
+                                ["Failed to negotiate caps: {:?}", caps]
+                            );
+                            gst::FlowError::NotNegotiated
+                        })?;
+
+                        let _ = element
+                            .post_message(&gst::Message::new_latency().src(Some(element)).build());
+                    }
+
+                    return Ok(buffer);
                 }
-
-                Ok(buffer)
+                ReceiverItem::Gap(pts, duration) => self.push_gap(element, pts, duration),
+                ReceiverItem::Timeout => return Err(gst::FlowError::Eos),
+                ReceiverItem::Flushing => return Err(gst::FlowError::Flushing),
+                ReceiverItem::Error(err) => return Err(err),
             }
-            ReceiverItem::Flushing => Err(gst::FlowError::Flushing),
-            ReceiverItem::Timeout => Err(gst::FlowError::Eos),
-            ReceiverItem::Error(err) => Err(err),
         }
     }
 }
 
+impl NdiAudioSrc {
+    // Lets downstream know that no data is coming for a while, once caps were configured
+    fn push_gap(&self, element: &gst_base::BaseSrc, pts: gst::ClockTime, duration: gst::ClockTime) {
+        if self.state.lock().unwrap().info.is_none() {
+            return;
+        }
+
+        gst_debug!(
+            self.cat,
+            obj: element,
+            "Pushing gap at {} with duration {}",
+            pts,
+            duration
+        );
+        element
+            .get_static_pad("src")
+            .unwrap()
+            .push_event(gst::Event::new_gap(pts, duration).build());
+    }
+}
+
 pub fn register(plugin: &gst::Plugin) -> Result<(), glib::BoolError> {
     gst::Element::register(
         Some(plugin),
@@ -0,0 +1,1442 @@
+use glib;
+use glib::subclass;
+use glib::subclass::prelude::*;
+use gst;
+use gst::prelude::*;
+use gst::subclass::prelude::*;
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::{cmp, i32, thread, time, u32};
+
+use crate::send::*;
+
+use crate::SendClockMode;
+use crate::TimecodeMode;
+use crate::DEFAULT_RECEIVER_NDI_NAME;
+
+#[derive(Debug)]
+struct Settings {
+    ndi_name: String,
+    groups: Option<String>,
+    timecode_mode: TimecodeMode,
+    async_send: bool,
+    drop_without_connections: bool,
+    connection_metadata: Option<String>,
+    clocking: SendClockMode,
+    stream_timeout: u32,
+    sync: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            ndi_name: DEFAULT_RECEIVER_NDI_NAME.clone(),
+            groups: None,
+            timecode_mode: TimecodeMode::Synthesize,
+            async_send: false,
+            drop_without_connections: false,
+            connection_metadata: None,
+            clocking: SendClockMode::Disabled,
+            stream_timeout: 1000,
+            sync: true,
+        }
+    }
+}
+
+static PROPERTIES: [subclass::Property; 12] = [
+    subclass::Property("ndi-name", |name| {
+        glib::ParamSpec::string(
+            name,
+            "NDI Name",
+            "The name of the NDI stream",
+            None,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("groups", |name| {
+        glib::ParamSpec::string(
+            name,
+            "Groups",
+            "Comma separated list of NDI groups to announce the stream in",
+            None,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("timecode-mode", |name| {
+        glib::ParamSpec::enum_(
+            name,
+            "Timecode Mode",
+            "Source of the NDI timecode of sent frames",
+            TimecodeMode::static_type(),
+            TimecodeMode::Synthesize as i32,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("async-send", |name| {
+        glib::ParamSpec::boolean(
+            name,
+            "Async Send",
+            "Send video frames asynchronously without copying them",
+            false,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("on-program", |name| {
+        glib::ParamSpec::boolean(
+            name,
+            "On Program",
+            "Whether the sent stream is on program of any receiver",
+            false,
+            glib::ParamFlags::READABLE,
+        )
+    }),
+    subclass::Property("on-preview", |name| {
+        glib::ParamSpec::boolean(
+            name,
+            "On Preview",
+            "Whether the sent stream is on preview of any receiver",
+            false,
+            glib::ParamFlags::READABLE,
+        )
+    }),
+    subclass::Property("connections", |name| {
+        glib::ParamSpec::int(
+            name,
+            "Connections",
+            "Number of receivers connected to the sender",
+            0,
+            i32::MAX,
+            0,
+            glib::ParamFlags::READABLE,
+        )
+    }),
+    subclass::Property("drop-without-connections", |name| {
+        glib::ParamSpec::boolean(
+            name,
+            "Drop Without Connections",
+            "Drop frames instead of sending them while no receiver is connected",
+            false,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("connection-metadata", |name| {
+        glib::ParamSpec::string(
+            name,
+            "Connection Metadata",
+            "Additional XML metadata sent to every receiver when it connects",
+            None,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("clocking", |name| {
+        glib::ParamSpec::enum_(
+            name,
+            "Clocking",
+            "Frames based on which the NDI SDK paces the output instead of syncing on the clock",
+            SendClockMode::static_type(),
+            SendClockMode::Disabled as i32,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("stream-timeout", |name| {
+        glib::ParamSpec::uint(
+            name,
+            "Stream Timeout",
+            "Time in ms to wait for data on the other pad before sending without it, 0 to wait forever",
+            0,
+            u32::MAX,
+            1000,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("sync", |name| {
+        glib::ParamSpec::boolean(
+            name,
+            "Sync",
+            "Sync on the clock unless the NDI SDK is clocking",
+            true,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+];
+
+// Buffers queued per stream are sent at the latest once this much data is queued, after which
+// the chain function waits for the render thread
+const MAX_QUEUE_TIME: gst::ClockTime = gst::SECOND;
+
+#[derive(Clone)]
+enum StreamInfo {
+    Video(gst_video::VideoInfo),
+    Audio(gst_audio::AudioInfo),
+}
+
+// Buffer waiting to be sent, together with the caps and segment it was received with
+struct QueuedBuffer {
+    running_time: gst::ClockTime,
+    buffer: gst::Buffer,
+    info: StreamInfo,
+    segment: gst::FormattedSegment<gst::ClockTime>,
+}
+
+// Per sink pad state. Buffers are queued here until the buffers of the other pad allow to
+// decide which one has to be sent next
+struct Stream {
+    pad: gst::Pad,
+    info: Option<StreamInfo>,
+    segment: gst::FormattedSegment<gst::ClockTime>,
+    queue: VecDeque<QueuedBuffer>,
+    // Running time up to which buffers or gaps were received on this stream
+    position: gst::ClockTime,
+    // Whether a buffer or gap was received since prerolling was requested the last time
+    has_data: bool,
+    eos: bool,
+    flushing: bool,
+    // Set when the other stream stopped waiting for this one, until new data arrives here
+    timed_out: bool,
+}
+
+impl Stream {
+    fn new(pad: gst::Pad) -> Self {
+        Self {
+            pad,
+            info: None,
+            segment: gst::FormattedSegment::new(),
+            queue: VecDeque::new(),
+            position: gst::CLOCK_TIME_NONE,
+            has_data: false,
+            eos: false,
+            flushing: false,
+            timed_out: false,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.segment = gst::FormattedSegment::new();
+        self.queue.clear();
+        self.position = gst::CLOCK_TIME_NONE;
+        self.has_data = false;
+        self.eos = false;
+        self.flushing = false;
+        self.timed_out = false;
+    }
+
+    fn update_position(&mut self, timestamp: gst::ClockTime, duration: gst::ClockTime) {
+        let end = if duration.is_some() {
+            timestamp + duration
+        } else {
+            timestamp
+        };
+        let running_time = self.segment.to_running_time(end);
+
+        if running_time.is_some() && (self.position.is_none() || running_time > self.position) {
+            self.position = running_time;
+        }
+        self.has_data = true;
+        self.timed_out = false;
+    }
+
+    // Whether a buffer of the other stream with the given running time can be sent without
+    // waiting for more data on this stream
+    fn is_after(&self, running_time: gst::ClockTime) -> bool {
+        self.eos
+            || self.timed_out
+            || running_time.is_none()
+            || (self.position.is_some() && self.position >= running_time)
+    }
+
+    // Whether the chain function has to wait before queueing another buffer
+    fn is_full(&self) -> bool {
+        let (front, back) = match (self.queue.front(), self.queue.back()) {
+            (Some(front), Some(back)) => (front.running_time, back.running_time),
+            _ => return false,
+        };
+
+        // Buffers without running time are sent right away, so don't queue more than one
+        if front.is_none() || back.is_none() {
+            return true;
+        }
+
+        back >= front + MAX_QUEUE_TIME
+    }
+}
+
+// What the render thread has to do next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Next {
+    Nothing,
+    // A buffer is queued but the other stream might still have earlier data
+    Waiting,
+    Video,
+    Audio,
+}
+
+struct State {
+    video: Option<Stream>,
+    audio: Option<Stream>,
+    // Result of sending the last buffer, returned by the chain functions
+    flow: Result<gst::FlowSuccess, gst::FlowError>,
+    // Set while the render thread is running
+    started: bool,
+    // Set while the asynchronous state change to PAUSED waits for data on all streams
+    need_preroll: bool,
+    playing: bool,
+    // Whether the NDI SDK paces the output, in which case syncing on the clock is disabled
+    clocked: bool,
+    // Configured with the latency event
+    latency: gst::ClockTime,
+    eos_posted: bool,
+    shutdown: bool,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            video: None,
+            audio: None,
+            flow: Ok(gst::FlowSuccess::Ok),
+            started: false,
+            need_preroll: false,
+            playing: false,
+            clocked: false,
+            latency: 0.into(),
+            eos_posted: false,
+            shutdown: false,
+        }
+    }
+}
+
+impl State {
+    fn stream(&self, pad: &gst::Pad) -> Option<&Stream> {
+        self.video
+            .as_ref()
+            .filter(|s| s.pad == *pad)
+            .or_else(|| self.audio.as_ref().filter(|s| s.pad == *pad))
+    }
+
+    fn stream_mut(&mut self, pad: &gst::Pad) -> Option<&mut Stream> {
+        let State {
+            ref mut video,
+            ref mut audio,
+            ..
+        } = *self;
+
+        video
+            .as_mut()
+            .filter(|s| s.pad == *pad)
+            .or_else(|| audio.as_mut().filter(|s| s.pad == *pad))
+    }
+
+    fn streams_mut(&mut self) -> impl Iterator<Item = &mut Stream> {
+        self.video.iter_mut().chain(self.audio.iter_mut())
+    }
+
+    fn is_video_pad(&self, pad: &gst::Pad) -> bool {
+        self.video.as_ref().map(|s| s.pad == *pad).unwrap_or(false)
+    }
+
+    fn is_eos(&self) -> bool {
+        self.video.as_ref().map(|s| s.eos).unwrap_or(true)
+            && self.audio.as_ref().map(|s| s.eos).unwrap_or(true)
+    }
+
+    // Whether every stream received data or is not waited for anymore, which completes the
+    // preroll
+    fn is_prerolled(&self) -> bool {
+        let mut streams = self.video.iter().chain(self.audio.iter()).peekable();
+
+        streams.peek().is_some() && streams.all(|s| s.has_data || s.eos || s.timed_out)
+    }
+
+    // Whether no data is available for prerolling again when going back to PAUSED
+    fn is_empty(&self) -> bool {
+        !self.is_eos()
+            && self
+                .video
+                .iter()
+                .chain(self.audio.iter())
+                .all(|s| s.queue.is_empty())
+    }
+
+    // A buffer can only be sent once it is known that the other stream has no earlier data
+    // that has to be sent first
+    fn next(&self) -> Next {
+        let video_head = self
+            .video
+            .as_ref()
+            .and_then(|s| s.queue.front())
+            .map(|b| b.running_time);
+        let audio_head = self
+            .audio
+            .as_ref()
+            .and_then(|s| s.queue.front())
+            .map(|b| b.running_time);
+
+        match (video_head, audio_head) {
+            (None, None) => Next::Nothing,
+            (Some(video_head), Some(audio_head)) => {
+                if video_head <= audio_head {
+                    Next::Video
+                } else {
+                    Next::Audio
+                }
+            }
+            (Some(video_head), None) => {
+                if self
+                    .audio
+                    .as_ref()
+                    .map(|s| s.is_after(video_head))
+                    .unwrap_or(true)
+                {
+                    Next::Video
+                } else {
+                    Next::Waiting
+                }
+            }
+            (None, Some(audio_head)) => {
+                if self
+                    .video
+                    .as_ref()
+                    .map(|s| s.is_after(audio_head))
+                    .unwrap_or(true)
+                {
+                    Next::Audio
+                } else {
+                    Next::Waiting
+                }
+            }
+        }
+    }
+}
+
+pub(crate) struct NdiSink {
+    cat: gst::DebugCategory,
+    settings: Mutex<Settings>,
+    state: Mutex<State>,
+    sender: Sender,
+    // Notified whenever buffers were queued or sent, or the state of a stream changed
+    cond: Condvar,
+    // Thread sending the queued buffers of all streams in running time order
+    thread: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+impl ObjectSubclass for NdiSink {
+    const NAME: &'static str = "RsNDISink";
+    type ParentType = gst::Element;
+    type Instance = gst::subclass::ElementInstanceStruct<Self>;
+    type Class = subclass::simple::ClassStruct<Self>;
+
+    glib_object_subclass!();
+
+    fn new() -> Self {
+        let cat = gst::DebugCategory::new(
+            "ndisink",
+            gst::DebugColorFlags::empty(),
+            Some("NewTek NDI Sink"),
+        );
+
+        Self {
+            cat,
+            settings: Mutex::new(Default::default()),
+            state: Mutex::new(Default::default()),
+            sender: Sender::new(cat),
+            cond: Condvar::new(),
+            thread: Mutex::new(None),
+        }
+    }
+
+    fn class_init(klass: &mut subclass::simple::ClassStruct<Self>) {
+        klass.set_metadata(
+            "NewTek NDI Sink",
+            "Sink/Audio/Video",
+            "NewTek NDI sink sending audio and video with a single NDI sender",
+            "Ruben Gonzalez <rubenrua@teltek.es>, Daniel Vilar <daniel.peiteado@teltek.es>, Sebastian Dröge <sebastian@centricular.com>, Luke Moscrop <luke.moscrop@bbc.co.uk>",
+        );
+
+        let caps = gst::Caps::new_simple(
+            "video/x-raw",
+            &[
+                (
+                    "format",
+                    &gst::List::new(&[
+                        &gst_video::VideoFormat::Uyvy.to_string(),
+                        &gst_video::VideoFormat::Yv12.to_string(),
+                        &gst_video::VideoFormat::Nv12.to_string(),
+                        &gst_video::VideoFormat::I420.to_string(),
+                        &gst_video::VideoFormat::Bgra.to_string(),
+                        &gst_video::VideoFormat::Bgrx.to_string(),
+                        &gst_video::VideoFormat::Rgba.to_string(),
+                        &gst_video::VideoFormat::Rgbx.to_string(),
+                        &gst_video::VideoFormat::Ayuv.to_string(),
+                    ]),
+                ),
+                ("width", &gst::IntRange::<i32>::new(0, i32::MAX)),
+                ("height", &gst::IntRange::<i32>::new(0, i32::MAX)),
+                (
+                    "framerate",
+                    &gst::FractionRange::new(
+                        gst::Fraction::new(0, 1),
+                        gst::Fraction::new(i32::MAX, 1),
+                    ),
+                ),
+            ],
+        );
+
+        #[cfg(feature = "interlaced-fields")]
+        let caps = {
+            let mut tmp = caps.copy();
+            {
+                let tmp = tmp.get_mut().unwrap();
+                tmp.set_features_simple(Some(gst::CapsFeatures::new(&["format:Interlaced"])));
+            }
+
+            let mut caps = caps;
+            {
+                let caps = caps.get_mut().unwrap();
+                caps.append(tmp);
+            }
+
+            caps
+        };
+
+        let video_pad_template = gst::PadTemplate::new(
+            "video",
+            gst::PadDirection::Sink,
+            gst::PadPresence::Request,
+            &caps,
+        )
+        .unwrap();
+        klass.add_pad_template(video_pad_template);
+
+        let caps = gst::Caps::new_simple(
+            "audio/x-raw",
+            &[
+                ("format", &gst_audio::AUDIO_FORMAT_F32.to_string()),
+                ("rate", &gst::IntRange::<i32>::new(0, i32::MAX)),
+                ("channels", &gst::IntRange::<i32>::new(0, i32::MAX)),
+                (
+                    "layout",
+                    &gst::List::new(&[&"interleaved", &"non-interleaved"]),
+                ),
+            ],
+        );
+
+        let caps = {
+            let mut caps = caps;
+            {
+                let caps = caps.get_mut().unwrap();
+                caps.append(gst::Caps::new_simple(
+                    "audio/x-raw",
+                    &[
+                        (
+                            "format",
+                            &gst::List::new(&[
+                                &gst_audio::AUDIO_FORMAT_S32.to_string(),
+                                &gst_audio::AUDIO_FORMAT_S16.to_string(),
+                            ]),
+                        ),
+                        ("rate", &gst::IntRange::<i32>::new(0, i32::MAX)),
+                        ("channels", &gst::IntRange::<i32>::new(0, i32::MAX)),
+                        ("layout", &"interleaved"),
+                    ],
+                ));
+            }
+            caps
+        };
+
+        let audio_pad_template = gst::PadTemplate::new(
+            "audio",
+            gst::PadDirection::Sink,
+            gst::PadPresence::Request,
+            &caps,
+        )
+        .unwrap();
+        klass.add_pad_template(audio_pad_template);
+
+        klass.install_properties(&PROPERTIES);
+
+        klass.add_signal(
+            "tally-changed",
+            glib::SignalFlags::RUN_LAST,
+            &[bool::static_type(), bool::static_type()],
+            glib::Type::Unit,
+        );
+
+        klass.add_signal(
+            "metadata-received",
+            glib::SignalFlags::RUN_LAST,
+            &[String::static_type()],
+            glib::Type::Unit,
+        );
+
+        klass.add_action_signal(
+            "send-metadata",
+            &[String::static_type()],
+            bool::static_type(),
+            |args| {
+                let element = args[0].get::<gst::Element>().unwrap();
+                let data = args[1].get::<String>().unwrap_or_default();
+                let imp = Self::from_instance(&element);
+
+                Some(imp.sender.send_metadata(&element, &data).to_value())
+            },
+        );
+    }
+}
+
+impl ObjectImpl for NdiSink {
+    glib_object_impl!();
+
+    fn constructed(&self, obj: &glib::Object) {
+        self.parent_constructed(obj);
+
+        let element = obj.downcast_ref::<gst::Element>().unwrap();
+        element.set_element_flags(gst::ElementFlags::SINK);
+    }
+
+    fn set_property(&self, obj: &glib::Object, id: usize, value: &glib::Value) {
+        let prop = &PROPERTIES[id];
+        let element = obj.downcast_ref::<gst::Element>().unwrap();
+
+        match *prop {
+            subclass::Property("ndi-name", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let ndi_name = value
+                    .get()
+                    .unwrap_or_else(|| DEFAULT_RECEIVER_NDI_NAME.clone());
+
+                settings.ndi_name = ndi_name;
+            }
+            subclass::Property("groups", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let groups = value.get();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing groups from {:?} to {:?}",
+                    settings.groups,
+                    groups,
+                );
+                settings.groups = groups;
+            }
+            subclass::Property("timecode-mode", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let timecode_mode = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing timecode mode from {:?} to {:?}",
+                    settings.timecode_mode,
+                    timecode_mode
+                );
+                settings.timecode_mode = timecode_mode;
+            }
+            subclass::Property("async-send", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let async_send = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing async send from {} to {}",
+                    settings.async_send,
+                    async_send
+                );
+                settings.async_send = async_send;
+            }
+            subclass::Property("drop-without-connections", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let drop_without_connections = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing drop without connections from {} to {}",
+                    settings.drop_without_connections,
+                    drop_without_connections
+                );
+                settings.drop_without_connections = drop_without_connections;
+            }
+            subclass::Property("clocking", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let clocking = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing clocking from {:?} to {:?}",
+                    settings.clocking,
+                    clocking
+                );
+                settings.clocking = clocking;
+            }
+            subclass::Property("sync", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let sync = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing sync from {} to {}",
+                    settings.sync,
+                    sync
+                );
+                settings.sync = sync;
+                self.cond.notify_all();
+            }
+            subclass::Property("stream-timeout", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let stream_timeout = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing stream timeout from {} to {}",
+                    settings.stream_timeout,
+                    stream_timeout
+                );
+                settings.stream_timeout = stream_timeout;
+            }
+            subclass::Property("connection-metadata", ..) => {
+                let connection_metadata: Option<String> = value.get();
+                {
+                    let mut settings = self.settings.lock().unwrap();
+                    gst_debug!(
+                        self.cat,
+                        obj: element,
+                        "Changing connection metadata from {:?} to {:?}",
+                        settings.connection_metadata,
+                        connection_metadata
+                    );
+                    settings.connection_metadata = connection_metadata.clone();
+                }
+
+                self.sender.set_connection_metadata(
+                    element,
+                    connection_metadata.as_ref().map(String::as_str),
+                );
+            }
+            _ => unimplemented!(),
+        }
+    }
+
+    fn get_property(&self, _obj: &glib::Object, id: usize) -> Result<glib::Value, ()> {
+        let prop = &PROPERTIES[id];
+
+        match *prop {
+            subclass::Property("ndi-name", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.ndi_name.to_value())
+            }
+            subclass::Property("groups", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.groups.to_value())
+            }
+            subclass::Property("timecode-mode", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.timecode_mode.to_value())
+            }
+            subclass::Property("async-send", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.async_send.to_value())
+            }
+            subclass::Property("on-program", ..) => Ok(self.sender.on_program().to_value()),
+            subclass::Property("on-preview", ..) => Ok(self.sender.on_preview().to_value()),
+            subclass::Property("connections", ..) => Ok(self.sender.connections().to_value()),
+            subclass::Property("drop-without-connections", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.drop_without_connections.to_value())
+            }
+            subclass::Property("connection-metadata", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.connection_metadata.to_value())
+            }
+            subclass::Property("clocking", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.clocking.to_value())
+            }
+            subclass::Property("stream-timeout", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.stream_timeout.to_value())
+            }
+            subclass::Property("sync", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.sync.to_value())
+            }
+            _ => unimplemented!(),
+        }
+    }
+}
+
+impl ElementImpl for NdiSink {
+    fn change_state(
+        &self,
+        element: &gst::Element,
+        transition: gst::StateChange,
+    ) -> Result<gst::StateChangeSuccess, gst::StateChangeError> {
+        // Set if the state change only completes once the render thread prerolled
+        let mut is_async = false;
+
+        match transition {
+            gst::StateChange::ReadyToPaused => {
+                let mut state = self.state.lock().unwrap();
+                let settings = self.settings.lock().unwrap();
+
+                let (clock_video, clock_audio) = match settings.clocking {
+                    SendClockMode::Auto => (state.video.is_some(), state.video.is_none()),
+                    SendClockMode::Video => (true, false),
+                    SendClockMode::Audio => (false, true),
+                    SendClockMode::Disabled => (false, false),
+                };
+                let sender = create_send_instance(
+                    settings.ndi_name.clone(),
+                    settings.groups.as_ref().map(String::as_str),
+                    clock_video,
+                    clock_audio,
+                )
+                .map_err(|err| {
+                    gst_element_error!(
+                        element,
+                        gst::ResourceError::OpenWrite,
+                        [
+                            "Failed to create NDI sender '{}': {}",
+                            settings.ndi_name,
+                            err
+                        ]
+                    );
+                    gst::StateChangeError
+                })?;
+
+                self.sender.start(
+                    element,
+                    sender,
+                    settings.connection_metadata.as_ref().map(String::as_str),
+                );
+
+                // The state change completes asynchronously once data arrived on all pads
+                state.started = true;
+                state.need_preroll = true;
+                is_async = true;
+                state.clocked = clock_video || clock_audio;
+                if state.clocked && settings.sync {
+                    gst_debug!(self.cat, obj: element, "Not syncing as the NDI SDK is clocking");
+                }
+                drop(settings);
+                drop(state);
+
+                let element_clone = element.clone();
+                let thread = thread::spawn(move || {
+                    let sink = Self::from_instance(&element_clone);
+                    sink.render_loop(&element_clone);
+                });
+                *self.thread.lock().unwrap() = Some(thread);
+
+                let _ = element
+                    .post_message(&gst::Message::new_async_start().src(Some(element)).build());
+            }
+            gst::StateChange::PausedToPlaying => {
+                let mut state = self.state.lock().unwrap();
+                state.playing = true;
+                is_async = state.need_preroll;
+                self.cond.notify_all();
+            }
+            gst::StateChange::PlayingToPaused => {
+                // Stop sending, and if nothing is queued wait again for data before completing
+                // the state change
+                let mut state = self.state.lock().unwrap();
+                state.playing = false;
+                if !state.need_preroll && state.is_empty() {
+                    state.need_preroll = true;
+                    for stream in state.streams_mut() {
+                        stream.has_data = false;
+                    }
+                    is_async = true;
+                    drop(state);
+
+                    let _ = element
+                        .post_message(&gst::Message::new_async_start().src(Some(element)).build());
+                }
+                self.cond.notify_all();
+            }
+            gst::StateChange::PausedToReady => {
+                // Stop the render thread and wake up all streaming threads that wait for
+                // space in the queue
+                {
+                    let mut state = self.state.lock().unwrap();
+                    state.started = false;
+                    state.shutdown = true;
+                    for stream in state.streams_mut() {
+                        stream.flushing = true;
+                        stream.queue.clear();
+                    }
+                    self.cond.notify_all();
+                }
+
+                let thread = self.thread.lock().unwrap().take();
+                if let Some(thread) = thread {
+                    let _ = thread.join();
+                }
+            }
+            _ => (),
+        }
+
+        let res = self.parent_change_state(element, transition)?;
+
+        if transition == gst::StateChange::PausedToReady {
+            self.sender.stop();
+
+            let mut state = self.state.lock().unwrap();
+            let video = state.video.take();
+            let audio = state.audio.take();
+            *state = State {
+                video,
+                audio,
+                ..State::default()
+            };
+            for stream in state.streams_mut() {
+                stream.reset();
+                stream.info = None;
+            }
+        }
+
+        if is_async {
+            Ok(gst::StateChangeSuccess::Async)
+        } else {
+            Ok(res)
+        }
+    }
+
+    fn send_event(&self, element: &gst::Element, event: gst::Event) -> bool {
+        use gst::EventView;
+
+        if let EventView::Latency(ev) = event.view() {
+            let latency = ev.get_latency();
+            gst_debug!(self.cat, obj: element, "Configuring latency {}", latency);
+            self.state.lock().unwrap().latency = latency;
+            self.cond.notify_all();
+        }
+
+        self.parent_send_event(element, event)
+    }
+
+    fn query(&self, element: &gst::Element, query: &mut gst::QueryRef) -> bool {
+        use gst::QueryView;
+
+        match query.view_mut() {
+            QueryView::Latency(ref mut q) => {
+                let pads = {
+                    let state = self.state.lock().unwrap();
+                    state
+                        .video
+                        .iter()
+                        .chain(state.audio.iter())
+                        .map(|s| s.pad.clone())
+                        .collect::<Vec<_>>()
+                };
+
+                // Combine the latency of all upstream branches, like BaseSink does for its
+                // single one
+                let mut live = false;
+                let mut min = gst::ClockTime::from(0);
+                let mut max = gst::CLOCK_TIME_NONE;
+                for pad in pads {
+                    let mut peer_query = gst::Query::new_latency();
+                    if !pad.peer_query(&mut peer_query) {
+                        return false;
+                    }
+
+                    let (peer_live, peer_min, peer_max) = peer_query.get_result();
+                    if peer_live {
+                        live = true;
+                        min = cmp::max(min, peer_min);
+                        if max.is_none() || (peer_max.is_some() && peer_max < max) {
+                            max = peer_max;
+                        }
+                    }
+                }
+
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Returning latency live {} min {} max {}",
+                    live,
+                    min,
+                    max
+                );
+                q.set(live, min, max);
+                true
+            }
+            _ => self.parent_query(element, query),
+        }
+    }
+
+    fn request_new_pad(
+        &self,
+        element: &gst::Element,
+        templ: &gst::PadTemplate,
+        _name: Option<String>,
+        _caps: Option<&gst::Caps>,
+    ) -> Option<gst::Pad> {
+        let mut state = self.state.lock().unwrap();
+
+        let is_video = Some(templ) == element.get_pad_template("video").as_ref();
+        let (name, stream) = if is_video {
+            ("video", &mut state.video)
+        } else {
+            ("audio", &mut state.audio)
+        };
+
+        if stream.is_some() {
+            gst_error!(self.cat, obj: element, "Already have an {} pad", name);
+            return None;
+        }
+
+        let pad = gst::Pad::new_from_template(templ, Some(name));
+        pad.set_chain_function(|pad, parent, buffer| {
+            NdiSink::catch_panic_pad_function(
+                parent,
+                || Err(gst::FlowError::Error),
+                |sink, element| sink.sink_chain(pad, element, buffer),
+            )
+        });
+        pad.set_event_function(|pad, parent, event| {
+            NdiSink::catch_panic_pad_function(
+                parent,
+                || false,
+                |sink, element| sink.sink_event(pad, element, event),
+            )
+        });
+
+        *stream = Some(Stream::new(pad.clone()));
+        drop(state);
+
+        element.add_pad(&pad).ok()?;
+
+        Some(pad)
+    }
+
+    fn release_pad(&self, element: &gst::Element, pad: &gst::Pad) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.is_video_pad(pad) {
+                state.video = None;
+            } else {
+                state.audio = None;
+            }
+
+            // The remaining pad might have waited for this one
+            self.cond.notify_all();
+        }
+
+        let _ = element.remove_pad(pad);
+    }
+}
+
+impl NdiSink {
+    fn sink_chain(
+        &self,
+        pad: &gst::Pad,
+        element: &gst::Element,
+        buffer: gst::Buffer,
+    ) -> Result<gst::FlowSuccess, gst::FlowError> {
+        gst_trace!(self.cat, obj: pad, "Handling buffer {:?}", buffer);
+
+        // Only queue the buffer for the render thread, which might need data from the other
+        // pad before sending it. Waiting here only happens once enough data is queued so
+        // that both pads can be fed from the same streaming thread.
+        let mut state = self.state.lock().unwrap();
+        loop {
+            state.flow?;
+
+            let stream = state.stream(pad).ok_or(gst::FlowError::Flushing)?;
+            if stream.flushing {
+                return Err(gst::FlowError::Flushing);
+            } else if !stream.is_full() {
+                break;
+            }
+
+            state = self.cond.wait(state).unwrap();
+        }
+
+        let stream = state.stream_mut(pad).ok_or(gst::FlowError::Flushing)?;
+        let info = stream.info.clone().ok_or_else(|| {
+            gst_element_error!(
+                element,
+                gst::CoreError::Negotiation,
+                ["No caps on pad {}", pad.get_name()]
+            );
+            gst::FlowError::NotNegotiated
+        })?;
+
+        let running_time = stream.segment.to_running_time(buffer.get_pts());
+        stream.update_position(buffer.get_pts(), buffer.get_duration());
+        stream.queue.push_back(QueuedBuffer {
+            running_time,
+            buffer,
+            info,
+            segment: stream.segment.clone(),
+        });
+        self.cond.notify_all();
+
+        state.flow
+    }
+
+    fn sink_event(&self, pad: &gst::Pad, element: &gst::Element, event: gst::Event) -> bool {
+        use gst::EventView;
+
+        gst_log!(self.cat, obj: pad, "Handling event {:?}", event);
+
+        match event.view() {
+            EventView::Caps(ev) => {
+                let caps = ev.get_caps().to_owned();
+                let mut state = self.state.lock().unwrap();
+
+                let info = if state.is_video_pad(pad) {
+                    gst_video::VideoInfo::from_caps(&caps).map(StreamInfo::Video)
+                } else {
+                    gst_audio::AudioInfo::from_caps(&caps).map(StreamInfo::Audio)
+                };
+
+                match state.stream_mut(pad) {
+                    Some(stream) => {
+                        stream.info = info;
+                        stream.info.is_some()
+                    }
+                    None => false,
+                }
+            }
+            EventView::Segment(ev) => {
+                let segment = match ev.get_segment().clone().downcast::<gst::ClockTime>() {
+                    Ok(segment) => segment,
+                    Err(_) => {
+                        gst_element_error!(
+                            element,
+                            gst::StreamError::Format,
+                            ["Only Time segments supported"]
+                        );
+                        return false;
+                    }
+                };
+
+                let mut state = self.state.lock().unwrap();
+                if let Some(stream) = state.stream_mut(pad) {
+                    stream.segment = segment;
+                }
+
+                true
+            }
+            EventView::Gap(ev) => {
+                let (timestamp, duration) = ev.get();
+
+                let mut state = self.state.lock().unwrap();
+                if let Some(stream) = state.stream_mut(pad) {
+                    stream.update_position(timestamp, duration);
+                }
+                self.cond.notify_all();
+
+                true
+            }
+            EventView::Eos(..) => {
+                // The render thread posts the EOS message once everything is sent
+                let mut state = self.state.lock().unwrap();
+                if let Some(stream) = state.stream_mut(pad) {
+                    stream.eos = true;
+                }
+                self.cond.notify_all();
+
+                true
+            }
+            EventView::FlushStart(..) => {
+                let mut state = self.state.lock().unwrap();
+                if let Some(stream) = state.stream_mut(pad) {
+                    stream.flushing = true;
+                    stream.queue.clear();
+                }
+                self.sender.with_instance(SendInstance::flush_async);
+                self.cond.notify_all();
+
+                // Everything queued is gone now, so the state change has to wait for new data
+                // like in BaseSink
+                let lost_state = state.started && !state.need_preroll;
+                if lost_state {
+                    state.need_preroll = true;
+                    state.playing = false;
+                }
+                drop(state);
+
+                if lost_state {
+                    gst_debug!(self.cat, obj: element, "Lost state because of flushing");
+                    element.lost_state();
+                }
+
+                true
+            }
+            EventView::CustomDownstream(..) | EventView::CustomDownstreamOob(..) => {
+                if let Some(data) = get_metadata_from_event(&event) {
+                    self.sender.send_metadata(element, &data)
+                } else {
+                    pad.event_default(Some(element), event)
+                }
+            }
+            EventView::FlushStop(..) => {
+                let mut state = self.state.lock().unwrap();
+                if let Some(stream) = state.stream_mut(pad) {
+                    stream.reset();
+                }
+                state.flow = Ok(gst::FlowSuccess::Ok);
+                state.eos_posted = false;
+
+                true
+            }
+            _ => pad.event_default(Some(element), event),
+        }
+    }
+
+    // Sends all queued buffers of both pads in running time order until shutting down
+    fn render_loop(&self, element: &gst::Element) {
+        let mut waiting_since = None;
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if state.shutdown {
+                return;
+            }
+
+            let next = state.next();
+            if next == Next::Waiting {
+                state = self.wait_for_other_stream(element, state, &mut waiting_since);
+                continue;
+            }
+            waiting_since = None;
+
+            if state.need_preroll {
+                if !state.is_prerolled() {
+                    state = self.cond.wait(state).unwrap();
+                    continue;
+                }
+
+                // Complete the pending state change, which might directly continue to PLAYING
+                // and needs the state lock for that
+                state.need_preroll = false;
+                drop(state);
+
+                gst_debug!(self.cat, obj: element, "Prerolled");
+                let _ = element.continue_state(gst::StateChangeReturn::Success);
+                let _ = element.post_message(
+                    &gst::Message::new_async_done(gst::CLOCK_TIME_NONE)
+                        .src(Some(element))
+                        .build(),
+                );
+
+                state = self.state.lock().unwrap();
+                continue;
+            }
+
+            // Nothing is sent in PAUSED
+            if !state.playing {
+                state = self.cond.wait(state).unwrap();
+                continue;
+            }
+
+            let is_video = match next {
+                Next::Video => true,
+                Next::Audio => false,
+                Next::Waiting => unreachable!(),
+                Next::Nothing => {
+                    if state.is_eos() && !state.eos_posted {
+                        state.eos_posted = true;
+                        drop(state);
+
+                        self.sender.with_instance(SendInstance::flush_async);
+                        gst_debug!(self.cat, obj: element, "All pads are EOS");
+                        let _ = element
+                            .post_message(&gst::Message::new_eos().src(Some(element)).build());
+
+                        state = self.state.lock().unwrap();
+                    } else {
+                        state = self.cond.wait(state).unwrap();
+                    }
+                    continue;
+                }
+            };
+
+            if let Some(wait) = self.get_sync_wait(element, &state, is_video) {
+                state = self.cond.wait_timeout(state, wait).unwrap().0;
+                continue;
+            }
+
+            let stream = if is_video {
+                state.video.as_mut()
+            } else {
+                state.audio.as_mut()
+            };
+            let item = stream.and_then(|s| s.queue.pop_front()).unwrap();
+            // The chain function might wait for space in the queue
+            self.cond.notify_all();
+            drop(state);
+
+            let res = self.render(element, item);
+
+            state = self.state.lock().unwrap();
+            if let Err(err) = res {
+                state.flow = Err(err);
+                for stream in state.streams_mut() {
+                    stream.queue.clear();
+                }
+                self.cond.notify_all();
+            }
+        }
+    }
+
+    // How long to wait until the next buffer of the stream has to be sent according to the
+    // clock, if at all. This waits on the condition variable so that flushing, state changes
+    // and new configurations interrupt the wait, after which it is checked again.
+    fn get_sync_wait(
+        &self,
+        element: &gst::Element,
+        state: &State,
+        is_video: bool,
+    ) -> Option<time::Duration> {
+        if state.clocked || !self.settings.lock().unwrap().sync {
+            return None;
+        }
+
+        let stream = if is_video {
+            state.video.as_ref()
+        } else {
+            state.audio.as_ref()
+        };
+        let running_time = stream.and_then(|s| s.queue.front())?.running_time;
+        if running_time.is_none() {
+            return None;
+        }
+
+        let clock = element.get_clock()?;
+        let now = clock.get_time();
+        let target = element.get_base_time() + running_time + state.latency;
+        if now >= target {
+            return None;
+        }
+
+        gst_trace!(
+            self.cat,
+            obj: element,
+            "Waiting for clock time {}, now {}",
+            target,
+            now
+        );
+        Some(time::Duration::from_nanos((target - now).nseconds()?))
+    }
+
+    // Waits for data on the other stream while a buffer is queued. If there is none for too
+    // long, e.g. because the other stream is sparse, stop waiting for it until it has data
+    // again
+    fn wait_for_other_stream<'a>(
+        &self,
+        element: &gst::Element,
+        mut state: MutexGuard<'a, State>,
+        waiting_since: &mut Option<time::Instant>,
+    ) -> MutexGuard<'a, State> {
+        let stream_timeout = self.settings.lock().unwrap().stream_timeout;
+        if stream_timeout == 0 {
+            return self.cond.wait(state).unwrap();
+        }
+
+        let timeout = time::Duration::from_millis(stream_timeout.into());
+        let elapsed = waiting_since
+            .get_or_insert_with(time::Instant::now)
+            .elapsed();
+        if elapsed < timeout {
+            return self.cond.wait_timeout(state, timeout - elapsed).unwrap().0;
+        }
+
+        gst_debug!(
+            self.cat,
+            obj: element,
+            "No data on the other pad for {}ms, not waiting for it anymore",
+            stream_timeout
+        );
+        for stream in state.streams_mut() {
+            if stream.queue.is_empty() {
+                stream.timed_out = true;
+            }
+        }
+        *waiting_since = None;
+
+        state
+    }
+
+    fn render(&self, element: &gst::Element, item: QueuedBuffer) -> Result<(), gst::FlowError> {
+        let (timecode_mode, async_send, drop_without_connections) = {
+            let settings = self.settings.lock().unwrap();
+            (
+                settings.timecode_mode,
+                settings.async_send,
+                settings.drop_without_connections,
+            )
+        };
+
+        let QueuedBuffer {
+            buffer,
+            info,
+            segment,
+            ..
+        } = item;
+
+        if self
+            .sender
+            .should_drop(element, drop_without_connections, &buffer)
+        {
+            return Ok(());
+        }
+
+        let timecode = get_timecode(timecode_mode, &segment, buffer.as_ref());
+        match info {
+            StreamInfo::Video(ref info) => {
+                gst_trace!(self.cat, obj: element, "Sending video {:?}", buffer);
+                let frame = if async_send {
+                    create_ndi_send_video_frame_from_owned_buffer(info, buffer, timecode)
+                } else {
+                    create_ndi_send_video_frame_from_buffer(info, buffer.as_ref(), timecode)
+                }
+                .map_err(|err| {
+                    gst_element_error!(
+                        element,
+                        gst::StreamError::Format,
+                        ["Failed to create NDI video frame: {}", err]
+                    );
+                    gst::FlowError::Error
+                })?;
+
+                self.sender.with_instance(|sender| {
+                    if async_send {
+                        sender.send_video_async(frame);
+                    } else {
+                        sender.flush_async();
+                        sender.send_video(frame);
+                    }
+                });
+            }
+            StreamInfo::Audio(ref info) => {
+                gst_trace!(self.cat, obj: element, "Sending audio {:?}", buffer);
+                let frame =
+                    create_ndi_send_audio_frame_from_buffer(info, buffer.as_ref(), timecode)
+                        .map_err(|err| {
+                            gst_element_error!(
+                                element,
+                                gst::StreamError::Format,
+                                ["Failed to create NDI audio frame: {}", err]
+                            );
+                            gst::FlowError::Error
+                        })?;
+
+                self.sender.with_instance(|sender| sender.send_audio(frame));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn register(plugin: &gst::Plugin) -> Result<(), glib::BoolError> {
+    gst::Element::register(
+        Some(plugin),
+        "ndisink",
+        gst::Rank::None,
+        NdiSink::get_type(),
+    )
+}
@@ -0,0 +1,1279 @@
+use glib;
+use glib::subclass;
+use glib::subclass::prelude::*;
+use gst;
+use gst::prelude::*;
+use gst::subclass::prelude::*;
+use gst_base;
+use gst_video;
+
+use std::cmp;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time;
+use std::{i32, u32};
+
+use crate::ndi::*;
+use crate::ndisys;
+
+use crate::receiver::{
+    allow_video_fields, calculate_timestamp, create_audio_buffer, create_audio_info,
+    create_video_buffer, create_video_info, get_recv_color_format, video_meta_supported_downstream,
+    Observations, RECONNECT_DELAY_MAX, RECONNECT_DELAY_MIN,
+};
+use crate::RecvColorFormat;
+use crate::TimeoutAction;
+use crate::TimestampMode;
+use crate::DEFAULT_RECEIVER_NDI_NAME;
+
+#[derive(Debug, Clone)]
+struct Settings {
+    ndi_name: Option<String>,
+    ip_address: Option<String>,
+    groups: Option<String>,
+    extra_ips: Option<String>,
+    show_local_sources: bool,
+    connect_timeout: u32,
+    timeout: u32,
+    timeout_action: TimeoutAction,
+    receiver_ndi_name: String,
+    bandwidth: ndisys::NDIlib_recv_bandwidth_e,
+    color_format: RecvColorFormat,
+    timestamp_mode: TimestampMode,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            ndi_name: None,
+            ip_address: None,
+            groups: None,
+            extra_ips: None,
+            show_local_sources: true,
+            receiver_ndi_name: DEFAULT_RECEIVER_NDI_NAME.clone(),
+            connect_timeout: 10000,
+            timeout: 5000,
+            timeout_action: TimeoutAction::Eos,
+            bandwidth: ndisys::NDIlib_recv_bandwidth_highest,
+            color_format: RecvColorFormat::Auto,
+            timestamp_mode: TimestampMode::ReceiveTime,
+        }
+    }
+}
+
+static PROPERTIES: [subclass::Property; 12] = [
+    subclass::Property("ndi-name", |name| {
+        glib::ParamSpec::string(
+            name,
+            "NDI Name",
+            "NDI stream name of the sender",
+            None,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("ip-address", |name| {
+        glib::ParamSpec::string(
+            name,
+            "IP Address",
+            "IP address and port of the sender, e.g. 127.0.0.1:5961",
+            None,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("groups", |name| {
+        glib::ParamSpec::string(
+            name,
+            "Groups",
+            "Comma separated list of NDI groups to search for the sender in",
+            None,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("extra-ips", |name| {
+        glib::ParamSpec::string(
+            name,
+            "Extra IPs",
+            "Comma separated list of additional IP addresses to search for the sender on",
+            None,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("show-local-sources", |name| {
+        glib::ParamSpec::boolean(
+            name,
+            "Show Local Sources",
+            "Whether to consider senders running on this machine",
+            true,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("receiver-ndi-name", |name| {
+        glib::ParamSpec::string(
+            name,
+            "Receiver NDI Name",
+            "NDI stream name of this receiver",
+            Some(&*DEFAULT_RECEIVER_NDI_NAME),
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("connect-timeout", |name| {
+        glib::ParamSpec::uint(
+            name,
+            "Connect Timeout",
+            "Connection timeout in ms",
+            0,
+            u32::MAX,
+            10000,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("timeout", |name| {
+        glib::ParamSpec::uint(
+            name,
+            "Timeout",
+            "Receive timeout in ms",
+            0,
+            u32::MAX,
+            5000,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("timeout-action", |name| {
+        glib::ParamSpec::enum_(
+            name,
+            "Timeout Action",
+            "What to do if no frames are received for the receive timeout",
+            TimeoutAction::static_type(),
+            TimeoutAction::Eos as i32,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("bandwidth", |name| {
+        glib::ParamSpec::int(
+            name,
+            "Bandwidth",
+            "Bandwidth, -10 metadata-only, 10 audio-only, 100 highest",
+            -10,
+            100,
+            100,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("color-format", |name| {
+        glib::ParamSpec::enum_(
+            name,
+            "Color Format",
+            "Receive color format, auto selects one based on the downstream caps",
+            RecvColorFormat::static_type(),
+            RecvColorFormat::Auto as i32,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("timestamp-mode", |name| {
+        glib::ParamSpec::enum_(
+            name,
+            "Timestamp Mode",
+            "Timestamp information to use for outgoing PTS",
+            TimestampMode::static_type(),
+            TimestampMode::ReceiveTime as i32,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+];
+
+struct State {
+    recv: Option<RecvInstance>,
+    source: Option<Source<'static>>,
+    // Set while reconnecting after a timeout, with the delay before the next attempt
+    reconnect_delay: Option<time::Duration>,
+    // Shared between all pads so that audio, video and metadata timestamps are calculated
+    // with the same NDI to GStreamer time mapping
+    observations: Observations,
+    video_info: Option<gst_video::VideoInfo>,
+    video_meta_supported: bool,
+    audio_info: Option<gst_audio::AudioInfo>,
+    current_latency: gst::ClockTime,
+    last_frame: time::Instant,
+    flow_combiner: gst_base::UniqueFlowCombiner,
+}
+
+impl Default for State {
+    fn default() -> State {
+        State {
+            recv: None,
+            source: None,
+            reconnect_delay: None,
+            observations: Observations::new(),
+            video_info: None,
+            video_meta_supported: false,
+            audio_info: None,
+            current_latency: gst::CLOCK_TIME_NONE,
+            last_frame: time::Instant::now(),
+            flow_combiner: gst_base::UniqueFlowCombiner::new(),
+        }
+    }
+}
+
+pub(crate) struct NdiSrc {
+    cat: gst::DebugCategory,
+    settings: Mutex<Settings>,
+    state: Mutex<State>,
+    video_pad: gst::Pad,
+    audio_pad: gst::Pad,
+    metadata_pad: gst::Pad,
+    // Set while shutting down so that the streaming thread stops connecting and capturing
+    flushing: AtomicBool,
+    playing: AtomicBool,
+}
+
+lazy_static! {
+    static ref METADATA_CAPS: gst::Caps = gst::Caps::new_simple("application/x-ndi-metadata", &[]);
+}
+
+impl ObjectSubclass for NdiSrc {
+    const NAME: &'static str = "NdiSrc";
+    type ParentType = gst::Element;
+    type Instance = gst::subclass::ElementInstanceStruct<Self>;
+    type Class = subclass::simple::ClassStruct<Self>;
+
+    glib_object_subclass!();
+
+    fn new_with_class(klass: &subclass::simple::ClassStruct<Self>) -> Self {
+        let video_pad =
+            gst::Pad::new_from_template(&klass.get_pad_template("video").unwrap(), Some("video"));
+        let audio_pad =
+            gst::Pad::new_from_template(&klass.get_pad_template("audio").unwrap(), Some("audio"));
+        let metadata_pad = gst::Pad::new_from_template(
+            &klass.get_pad_template("metadata").unwrap(),
+            Some("metadata"),
+        );
+
+        for pad in &[&video_pad, &audio_pad, &metadata_pad] {
+            pad.set_query_function(|pad, parent, query| {
+                NdiSrc::catch_panic_pad_function(
+                    parent,
+                    || false,
+                    |src, element| src.src_query(pad, element, query),
+                )
+            });
+        }
+
+        Self {
+            cat: gst::DebugCategory::new(
+                "ndisrc",
+                gst::DebugColorFlags::empty(),
+                Some("NewTek NDI Source"),
+            ),
+            settings: Mutex::new(Default::default()),
+            state: Mutex::new(Default::default()),
+            video_pad,
+            audio_pad,
+            metadata_pad,
+            flushing: AtomicBool::new(true),
+            playing: AtomicBool::new(false),
+        }
+    }
+
+    fn class_init(klass: &mut subclass::simple::ClassStruct<Self>) {
+        klass.set_metadata(
+            "NewTek NDI Source",
+            "Source/Audio/Video",
+            "NewTek NDI source receiving audio, video and metadata with a single NDI receiver",
+            "Ruben Gonzalez <rubenrua@teltek.es>, Daniel Vilar <daniel.peiteado@teltek.es>, Sebastian Dröge <sebastian@centricular.com>",
+        );
+
+        let caps = gst::Caps::new_simple(
+            "video/x-raw",
+            &[
+                (
+                    "format",
+                    &gst::List::new(&[
+                        &gst_video::VideoFormat::Uyvy.to_string(),
+                        &gst_video::VideoFormat::Yv12.to_string(),
+                        &gst_video::VideoFormat::Nv12.to_string(),
+                        &gst_video::VideoFormat::I420.to_string(),
+                        &gst_video::VideoFormat::Bgra.to_string(),
+                        &gst_video::VideoFormat::Bgrx.to_string(),
+                        &gst_video::VideoFormat::Rgba.to_string(),
+                        &gst_video::VideoFormat::Rgbx.to_string(),
+                        &gst_video::VideoFormat::Ayuv.to_string(),
+                    ]),
+                ),
+                ("width", &gst::IntRange::<i32>::new(0, i32::MAX)),
+                ("height", &gst::IntRange::<i32>::new(0, i32::MAX)),
+                (
+                    "framerate",
+                    &gst::FractionRange::new(
+                        gst::Fraction::new(0, 1),
+                        gst::Fraction::new(i32::MAX, 1),
+                    ),
+                ),
+            ],
+        );
+
+        #[cfg(feature = "interlaced-fields")]
+        let caps = {
+            let mut tmp = caps.copy();
+            {
+                let tmp = tmp.get_mut().unwrap();
+                tmp.set_features_simple(Some(gst::CapsFeatures::new(&["format:Interlaced"])));
+            }
+
+            let mut caps = caps;
+            {
+                let caps = caps.get_mut().unwrap();
+                caps.append(tmp);
+            }
+
+            caps
+        };
+
+        let video_pad_template = gst::PadTemplate::new(
+            "video",
+            gst::PadDirection::Src,
+            gst::PadPresence::Always,
+            &caps,
+        )
+        .unwrap();
+        klass.add_pad_template(video_pad_template);
+
+        let caps = gst::Caps::new_simple(
+            "audio/x-raw",
+            &[
+                (
+                    "format",
+                    &gst::List::new(&[&gst_audio::AUDIO_FORMAT_S16.to_string()]),
+                ),
+                ("rate", &gst::IntRange::<i32>::new(1, i32::MAX)),
+                ("channels", &gst::IntRange::<i32>::new(1, i32::MAX)),
+                ("layout", &"interleaved"),
+            ],
+        );
+
+        let audio_pad_template = gst::PadTemplate::new(
+            "audio",
+            gst::PadDirection::Src,
+            gst::PadPresence::Always,
+            &caps,
+        )
+        .unwrap();
+        klass.add_pad_template(audio_pad_template);
+
+        let metadata_pad_template = gst::PadTemplate::new(
+            "metadata",
+            gst::PadDirection::Src,
+            gst::PadPresence::Always,
+            &*METADATA_CAPS,
+        )
+        .unwrap();
+        klass.add_pad_template(metadata_pad_template);
+
+        klass.install_properties(&PROPERTIES);
+    }
+}
+
+impl ObjectImpl for NdiSrc {
+    glib_object_impl!();
+
+    fn constructed(&self, obj: &glib::Object) {
+        self.parent_constructed(obj);
+
+        let element = obj.downcast_ref::<gst::Element>().unwrap();
+        element.add_pad(&self.video_pad).unwrap();
+        element.add_pad(&self.audio_pad).unwrap();
+        element.add_pad(&self.metadata_pad).unwrap();
+        element.set_element_flags(gst::ElementFlags::SOURCE);
+    }
+
+    fn set_property(&self, obj: &glib::Object, id: usize, value: &glib::Value) {
+        let prop = &PROPERTIES[id];
+        let element = obj.downcast_ref::<gst::Element>().unwrap();
+
+        match *prop {
+            subclass::Property("ndi-name", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let ndi_name = value.get();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing ndi-name from {:?} to {:?}",
+                    settings.ndi_name,
+                    ndi_name,
+                );
+                settings.ndi_name = ndi_name;
+            }
+            subclass::Property("ip-address", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let ip_address = value.get();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing ip from {:?} to {:?}",
+                    settings.ip_address,
+                    ip_address,
+                );
+                settings.ip_address = ip_address;
+            }
+            subclass::Property("groups", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let groups = value.get();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing groups from {:?} to {:?}",
+                    settings.groups,
+                    groups,
+                );
+                settings.groups = groups;
+            }
+            subclass::Property("extra-ips", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let extra_ips = value.get();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing extra-ips from {:?} to {:?}",
+                    settings.extra_ips,
+                    extra_ips,
+                );
+                settings.extra_ips = extra_ips;
+            }
+            subclass::Property("show-local-sources", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let show_local_sources = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing show-local-sources from {} to {}",
+                    settings.show_local_sources,
+                    show_local_sources,
+                );
+                settings.show_local_sources = show_local_sources;
+            }
+            subclass::Property("receiver-ndi-name", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let receiver_ndi_name = value.get();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing receiver-ndi-name from {:?} to {:?}",
+                    settings.receiver_ndi_name,
+                    receiver_ndi_name,
+                );
+                settings.receiver_ndi_name =
+                    receiver_ndi_name.unwrap_or_else(|| DEFAULT_RECEIVER_NDI_NAME.clone());
+            }
+            subclass::Property("connect-timeout", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let connect_timeout = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing connect-timeout from {} to {}",
+                    settings.connect_timeout,
+                    connect_timeout,
+                );
+                settings.connect_timeout = connect_timeout;
+            }
+            subclass::Property("timeout", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let timeout = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing timeout from {} to {}",
+                    settings.timeout,
+                    timeout,
+                );
+                settings.timeout = timeout;
+            }
+            subclass::Property("timeout-action", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let timeout_action = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing timeout action from {:?} to {:?}",
+                    settings.timeout_action,
+                    timeout_action,
+                );
+                settings.timeout_action = timeout_action;
+            }
+            subclass::Property("bandwidth", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let bandwidth = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing bandwidth from {} to {}",
+                    settings.bandwidth,
+                    bandwidth,
+                );
+                settings.bandwidth = bandwidth;
+            }
+            subclass::Property("color-format", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let color_format = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing color format from {:?} to {:?}",
+                    settings.color_format,
+                    color_format,
+                );
+                settings.color_format = color_format;
+            }
+            subclass::Property("timestamp-mode", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let timestamp_mode = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing timestamp mode from {:?} to {:?}",
+                    settings.timestamp_mode,
+                    timestamp_mode
+                );
+                if settings.timestamp_mode != timestamp_mode {
+                    let _ = element
+                        .post_message(&gst::Message::new_latency().src(Some(element)).build());
+                }
+                settings.timestamp_mode = timestamp_mode;
+            }
+            _ => unimplemented!(),
+        }
+    }
+
+    fn get_property(&self, _obj: &glib::Object, id: usize) -> Result<glib::Value, ()> {
+        let prop = &PROPERTIES[id];
+
+        match *prop {
+            subclass::Property("ndi-name", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.ndi_name.to_value())
+            }
+            subclass::Property("ip-address", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.ip_address.to_value())
+            }
+            subclass::Property("groups", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.groups.to_value())
+            }
+            subclass::Property("extra-ips", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.extra_ips.to_value())
+            }
+            subclass::Property("show-local-sources", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.show_local_sources.to_value())
+            }
+            subclass::Property("receiver-ndi-name", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.receiver_ndi_name.to_value())
+            }
+            subclass::Property("connect-timeout", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.connect_timeout.to_value())
+            }
+            subclass::Property("timeout", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.timeout.to_value())
+            }
+            subclass::Property("timeout-action", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.timeout_action.to_value())
+            }
+            subclass::Property("bandwidth", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.bandwidth.to_value())
+            }
+            subclass::Property("color-format", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.color_format.to_value())
+            }
+            subclass::Property("timestamp-mode", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.timestamp_mode.to_value())
+            }
+            _ => unimplemented!(),
+        }
+    }
+}
+
+impl ElementImpl for NdiSrc {
+    fn change_state(
+        &self,
+        element: &gst::Element,
+        transition: gst::StateChange,
+    ) -> Result<gst::StateChangeSuccess, gst::StateChangeError> {
+        match transition {
+            gst::StateChange::ReadyToPaused => {
+                let settings = self.settings.lock().unwrap();
+                if settings.ip_address.is_none() && settings.ndi_name.is_none() {
+                    gst_element_error!(
+                        element,
+                        gst::LibraryError::Settings,
+                        ["No IP address or NDI name given"]
+                    );
+                    return Err(gst::StateChangeError);
+                }
+            }
+            gst::StateChange::PausedToPlaying => {
+                self.playing.store(true, Ordering::SeqCst);
+            }
+            gst::StateChange::PlayingToPaused => {
+                self.playing.store(false, Ordering::SeqCst);
+            }
+            gst::StateChange::PausedToReady => {
+                self.flushing.store(true, Ordering::SeqCst);
+                let _ = self.video_pad.stop_task();
+            }
+            _ => (),
+        }
+
+        let mut success = self.parent_change_state(element, transition)?;
+
+        match transition {
+            gst::StateChange::ReadyToPaused => {
+                {
+                    let mut state = State::default();
+                    state.flow_combiner.add_pad(&self.video_pad);
+                    state.flow_combiner.add_pad(&self.audio_pad);
+                    state.flow_combiner.add_pad(&self.metadata_pad);
+                    *self.state.lock().unwrap() = state;
+                }
+                self.flushing.store(false, Ordering::SeqCst);
+
+                // All pads are pushed from a single streaming thread that captures all frame
+                // types from the same receiver
+                let element_weak = element.downgrade();
+                self.video_pad
+                    .start_task(move || {
+                        let element = match element_weak.upgrade() {
+                            Some(element) => element,
+                            None => return,
+                        };
+                        let src = Self::from_instance(&element);
+                        src.loop_fn(&element);
+                    })
+                    .map_err(|_| {
+                        gst_element_error!(
+                            element,
+                            gst::CoreError::StateChange,
+                            ["Failed to start streaming thread"]
+                        );
+                        gst::StateChangeError
+                    })?;
+
+                success = gst::StateChangeSuccess::NoPreroll;
+            }
+            gst::StateChange::PlayingToPaused => {
+                success = gst::StateChangeSuccess::NoPreroll;
+            }
+            gst::StateChange::PausedToReady => {
+                *self.state.lock().unwrap() = State::default();
+            }
+            _ => (),
+        }
+
+        Ok(success)
+    }
+}
+
+impl NdiSrc {
+    fn src_query(&self, pad: &gst::Pad, element: &gst::Element, query: &mut gst::QueryRef) -> bool {
+        use gst::QueryView;
+
+        match query.view_mut() {
+            QueryView::Latency(ref mut q) => {
+                let state = self.state.lock().unwrap();
+                let settings = self.settings.lock().unwrap();
+
+                if state.current_latency.is_some() {
+                    let min = if settings.timestamp_mode != TimestampMode::Timecode {
+                        state.current_latency
+                    } else {
+                        0.into()
+                    };
+
+                    let max = 5 * state.current_latency;
+
+                    gst_debug!(
+                        self.cat,
+                        obj: pad,
+                        "Returning latency min {} max {}",
+                        min,
+                        max
+                    );
+                    q.set(true, min, max);
+                    true
+                } else {
+                    false
+                }
+            }
+            _ => pad.query_default(Some(element), query),
+        }
+    }
+
+    fn connect(
+        &self,
+        element: &gst::Element,
+        settings: &Settings,
+    ) -> Result<Option<(RecvInstance, Source<'static>)>, gst::ErrorMessage> {
+        gst_debug!(self.cat, obj: element, "Starting NDI connection...");
+
+        let mut find = FindInstance::builder().show_local_sources(settings.show_local_sources);
+        if let Some(ref groups) = settings.groups {
+            find = find.groups(groups);
+        }
+        if let Some(ref extra_ips) = settings.extra_ips {
+            find = find.extra_ips(extra_ips);
+        }
+
+        let mut find = match find.build() {
+            None => {
+                return Err(gst_error_msg!(
+                    gst::CoreError::Negotiation,
+                    ["Cannot run NDI: NDIlib_find_create_v2 error"]
+                ));
+            }
+            Some(find) => find,
+        };
+
+        let ip_address = settings.ip_address.as_ref().map(|s| s.to_lowercase());
+
+        let timer = time::Instant::now();
+        let source = loop {
+            if self.flushing.load(Ordering::SeqCst) {
+                return Ok(None);
+            }
+
+            let new_sources = find.wait_for_sources(100);
+            let sources = find.get_current_sources();
+
+            gst_debug!(
+                self.cat,
+                obj: element,
+                "Total sources found in network {}",
+                sources.len(),
+            );
+
+            if new_sources {
+                let source = sources.iter().find(|s| {
+                    Some(s.ndi_name()) == settings.ndi_name.as_ref().map(String::as_str)
+                        || Some(&s.ip_address().to_lowercase()) == ip_address.as_ref()
+                });
+
+                if let Some(source) = source {
+                    break source.to_owned();
+                }
+            }
+
+            if timer.elapsed().as_millis() >= settings.connect_timeout as u128 {
+                return Err(gst_error_msg!(
+                    gst::ResourceError::NotFound,
+                    ["Stream not found"]
+                ));
+            }
+        };
+
+        gst_debug!(
+            self.cat,
+            obj: element,
+            "Connecting to NDI source with ndi-name '{}' and ip-address '{}'",
+            source.ndi_name(),
+            source.ip_address(),
+        );
+
+        let color_format = get_recv_color_format(self.cat, &self.video_pad, settings.color_format);
+        gst_debug!(self.cat, obj: element, "Using color format {:?}", color_format);
+
+        let recv = RecvInstance::builder(&source, &settings.receiver_ndi_name)
+            .bandwidth(settings.bandwidth)
+            .color_format(color_format)
+            .allow_video_fields(allow_video_fields(color_format))
+            .build();
+        let recv = match recv {
+            None => {
+                return Err(gst_error_msg!(
+                    gst::CoreError::Negotiation,
+                    ["Failed to connect to source"]
+                ));
+            }
+            Some(recv) => recv,
+        };
+
+        recv.set_tally(&Tally::default());
+
+        let enable_hw_accel = MetadataFrame::new(0, Some("<ndi_hwaccel enabled=\"true\"/>"));
+        recv.send_metadata(&enable_hw_accel);
+
+        gst_debug!(self.cat, obj: element, "Started NDI connection");
+
+        Ok(Some((recv, source)))
+    }
+
+    fn loop_fn(&self, element: &gst::Element) {
+        let settings = self.settings.lock().unwrap().clone();
+
+        let recv = self.state.lock().unwrap().recv.clone();
+        let recv = match recv {
+            Some(recv) => recv,
+            None => match self.connect(element, &settings) {
+                Ok(Some((recv, source))) => {
+                    let mut state = self.state.lock().unwrap();
+                    state.recv = Some(recv.clone());
+                    state.last_frame = time::Instant::now();
+                    let reconnected = state.reconnect_delay.take().is_some();
+                    drop(state);
+
+                    if reconnected {
+                        gst_debug!(self.cat, obj: element, "Reconnected");
+                        self.post_connection_message(element, "ndi-reconnected", &source);
+                    }
+                    self.state.lock().unwrap().source = Some(source);
+
+                    recv
+                }
+                Ok(None) => {
+                    gst_debug!(self.cat, obj: element, "Shutting down while connecting");
+                    let _ = self.video_pad.pause_task();
+                    return;
+                }
+                Err(err) => {
+                    let reconnect_delay = self.state.lock().unwrap().reconnect_delay;
+                    if let Some(delay) = reconnect_delay {
+                        gst_warning!(
+                            self.cat,
+                            obj: element,
+                            "Failed to reconnect: {:?}, retrying in {:?}",
+                            err,
+                            delay
+                        );
+                        self.push_gaps(element, &settings);
+                        self.wait_for_reconnect(delay);
+                        self.state.lock().unwrap().reconnect_delay =
+                            Some(cmp::min(delay * 2, RECONNECT_DELAY_MAX));
+                        return;
+                    }
+
+                    gst_error!(self.cat, obj: element, "Error while connecting: {:?}", err);
+                    element.post_error_message(&err);
+                    let _ = self.video_pad.pause_task();
+                    return;
+                }
+            },
+        };
+
+        let res = match recv.capture(true, true, true, 50) {
+            Err(_) => {
+                gst_element_error!(
+                    element,
+                    gst::ResourceError::Read,
+                    ["Error receiving frame"]
+                );
+                Err(gst::FlowError::Error)
+            }
+            Ok(None) => {
+                let last_frame = self.state.lock().unwrap().last_frame;
+                if last_frame.elapsed().as_millis() >= settings.timeout as u128 {
+                    self.handle_timeout(element, &settings)
+                } else {
+                    Ok(gst::FlowSuccess::Ok)
+                }
+            }
+            Ok(Some(frame)) => {
+                self.state.lock().unwrap().last_frame = time::Instant::now();
+
+                // Simply read all frames while not playing but don't copy them or anything to
+                // make sure that we're not accumulating anything here
+                if !self.playing.load(Ordering::SeqCst) {
+                    gst_debug!(self.cat, obj: element, "Not playing, dropping frame");
+                    Ok(gst::FlowSuccess::Ok)
+                } else {
+                    match frame {
+                        Frame::Video(frame) => self.handle_video_frame(element, &settings, frame),
+                        Frame::Audio(frame) => self.handle_audio_frame(element, &settings, &frame),
+                        Frame::Metadata(frame) => {
+                            self.handle_metadata_frame(element, &settings, &frame)
+                        }
+                    }
+                }
+            }
+        };
+
+        match res {
+            Ok(_) => (),
+            Err(gst::FlowError::Flushing) => {
+                gst_debug!(self.cat, obj: element, "Flushing");
+                let _ = self.video_pad.pause_task();
+            }
+            Err(gst::FlowError::Eos) => {
+                gst_debug!(self.cat, obj: element, "Signalling EOS");
+                for pad in &[&self.video_pad, &self.audio_pad, &self.metadata_pad] {
+                    pad.push_event(gst::Event::new_eos().build());
+                }
+                let _ = self.video_pad.pause_task();
+            }
+            Err(err) => {
+                gst_error!(self.cat, obj: element, "Streaming stopped, reason {:?}", err);
+                if err != gst::FlowError::Error {
+                    gst_element_error!(
+                        element,
+                        gst::StreamError::Failed,
+                        ["Internal data stream error"],
+                        ["streaming stopped, reason {:?}", err]
+                    );
+                }
+                for pad in &[&self.video_pad, &self.audio_pad, &self.metadata_pad] {
+                    pad.push_event(gst::Event::new_eos().build());
+                }
+                let _ = self.video_pad.pause_task();
+            }
+        }
+    }
+
+    fn handle_timeout(
+        &self,
+        element: &gst::Element,
+        settings: &Settings,
+    ) -> Result<gst::FlowSuccess, gst::FlowError> {
+        match settings.timeout_action {
+            TimeoutAction::Eos => {
+                gst_debug!(self.cat, obj: element, "Timed out -- assuming EOS");
+                Err(gst::FlowError::Eos)
+            }
+            TimeoutAction::Error => {
+                gst_element_error!(
+                    element,
+                    gst::ResourceError::Read,
+                    ["No frames received for {}ms", settings.timeout]
+                );
+                Err(gst::FlowError::Error)
+            }
+            TimeoutAction::Wait => {
+                gst_debug!(self.cat, obj: element, "Timed out -- waiting for more frames");
+                self.push_gaps(element, settings);
+                self.state.lock().unwrap().last_frame = time::Instant::now();
+                Ok(gst::FlowSuccess::Ok)
+            }
+            TimeoutAction::Reconnect => {
+                gst_debug!(self.cat, obj: element, "Timed out -- reconnecting");
+                self.push_gaps(element, settings);
+
+                let source = {
+                    let mut state = self.state.lock().unwrap();
+                    state.recv = None;
+                    state.observations = Observations::new();
+                    state.reconnect_delay = Some(RECONNECT_DELAY_MIN);
+                    state.source.take()
+                };
+                if let Some(source) = source {
+                    self.post_connection_message(element, "ndi-disconnected", &source);
+                }
+
+                Ok(gst::FlowSuccess::Ok)
+            }
+        }
+    }
+
+    // Waits before the next reconnection attempt unless shutting down in the meantime
+    fn wait_for_reconnect(&self, delay: time::Duration) {
+        let timer = time::Instant::now();
+        while timer.elapsed() < delay && !self.flushing.load(Ordering::SeqCst) {
+            thread::sleep(time::Duration::from_millis(100));
+        }
+    }
+
+    // Lets downstream know that no data is coming for a while on all pads with caps
+    fn push_gaps(&self, element: &gst::Element, settings: &Settings) {
+        if !self.playing.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let clock = match element.get_clock() {
+            None => return,
+            Some(clock) => clock,
+        };
+
+        let running_time = clock.get_time() - element.get_base_time();
+        let duration = gst::ClockTime::from_mseconds(settings.timeout as u64);
+
+        for pad in &[&self.video_pad, &self.audio_pad, &self.metadata_pad] {
+            if pad.get_current_caps().is_some() {
+                pad.push_event(gst::Event::new_gap(running_time, duration).build());
+            }
+        }
+    }
+
+    fn post_connection_message(&self, element: &gst::Element, name: &str, source: &Source) {
+        let s = gst::Structure::new(
+            name,
+            &[
+                ("ndi-name", &source.ndi_name()),
+                ("ip-address", &source.ip_address().to_lowercase()),
+            ],
+        );
+        let _ = element.post_message(&gst::Message::new_element(s).src(Some(element)).build());
+    }
+
+    fn handle_video_frame(
+        &self,
+        element: &gst::Element,
+        settings: &Settings,
+        video_frame: VideoFrame,
+    ) -> Result<gst::FlowSuccess, gst::FlowError> {
+        gst_debug!(self.cat, obj: element, "Received video frame {:?}", video_frame);
+
+        let duration = gst::SECOND
+            .mul_div_floor(
+                video_frame.frame_rate().1 as u64,
+                video_frame.frame_rate().0 as u64,
+            )
+            .unwrap_or(gst::CLOCK_TIME_NONE);
+
+        let (pts, duration) = match self.calculate_timestamp(
+            element,
+            settings,
+            video_frame.timestamp(),
+            video_frame.timecode(),
+            duration,
+        ) {
+            Some(res) => res,
+            None => return Ok(gst::FlowSuccess::Ok),
+        };
+
+        let info = create_video_info(element, &video_frame)?;
+
+        let caps = {
+            let mut state = self.state.lock().unwrap();
+            if state.video_info.as_ref() != Some(&info) {
+                let caps = info.to_caps().ok_or_else(|| {
+                    gst_element_error!(
+                        element,
+                        gst::ResourceError::Settings,
+                        ["Invalid video info received: {:?}", info]
+                    );
+                    gst::FlowError::NotNegotiated
+                })?;
+                state.video_info = Some(info.clone());
+                state.current_latency = duration;
+                drop(state);
+
+                let _ =
+                    element.post_message(&gst::Message::new_latency().src(Some(element)).build());
+
+                Some(caps)
+            } else {
+                None
+            }
+        };
+
+        // Configure downstream first so that video meta support is known for the first buffer
+        let video_meta_supported = match caps {
+            Some(caps) => {
+                self.configure_pad(element, &self.video_pad, Some(caps.clone()));
+
+                let video_meta_supported = video_meta_supported_downstream(&self.video_pad, &caps);
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Downstream supports video meta: {}",
+                    video_meta_supported
+                );
+                self.state.lock().unwrap().video_meta_supported = video_meta_supported;
+                video_meta_supported
+            }
+            None => self.state.lock().unwrap().video_meta_supported,
+        };
+
+        let buffer = create_video_buffer(
+            element,
+            pts,
+            duration,
+            &info,
+            video_frame,
+            video_meta_supported,
+        )?;
+
+        gst_log!(self.cat, obj: element, "Produced video buffer {:?}", buffer);
+
+        self.push_buffer(element, &self.video_pad, None, buffer)
+    }
+
+    fn handle_audio_frame(
+        &self,
+        element: &gst::Element,
+        settings: &Settings,
+        audio_frame: &AudioFrame,
+    ) -> Result<gst::FlowSuccess, gst::FlowError> {
+        gst_debug!(self.cat, obj: element, "Received audio frame {:?}", audio_frame);
+
+        let duration = gst::SECOND
+            .mul_div_floor(
+                audio_frame.no_samples() as u64,
+                audio_frame.sample_rate() as u64,
+            )
+            .unwrap_or(gst::CLOCK_TIME_NONE);
+
+        let (pts, duration) = match self.calculate_timestamp(
+            element,
+            settings,
+            audio_frame.timestamp(),
+            audio_frame.timecode(),
+            duration,
+        ) {
+            Some(res) => res,
+            None => return Ok(gst::FlowSuccess::Ok),
+        };
+
+        let info = create_audio_info(
+            element,
+            audio_frame,
+            gst_audio::AUDIO_FORMAT_S16,
+            gst_audio::AudioLayout::Interleaved,
+        )?;
+
+        let caps = {
+            let mut state = self.state.lock().unwrap();
+            if state.audio_info.as_ref() != Some(&info) {
+                let caps = info.to_caps().ok_or_else(|| {
+                    gst_element_error!(
+                        element,
+                        gst::ResourceError::Settings,
+                        ["Invalid audio info received: {:?}", info]
+                    );
+                    gst::FlowError::NotNegotiated
+                })?;
+                state.audio_info = Some(info.clone());
+                if state.current_latency.is_none() {
+                    state.current_latency = duration;
+                }
+
+                Some(caps)
+            } else {
+                None
+            }
+        };
+
+        let buffer = create_audio_buffer(element, pts, duration, &info, audio_frame, 0)?;
+
+        gst_log!(self.cat, obj: element, "Produced audio buffer {:?}", buffer);
+
+        self.push_buffer(element, &self.audio_pad, caps, buffer)
+    }
+
+    fn handle_metadata_frame(
+        &self,
+        element: &gst::Element,
+        settings: &Settings,
+        metadata_frame: &MetadataFrame,
+    ) -> Result<gst::FlowSuccess, gst::FlowError> {
+        gst_debug!(self.cat, obj: element, "Received metadata frame {:?}", metadata_frame);
+
+        let metadata = match metadata_frame.metadata() {
+            Some(metadata) => metadata,
+            None => return Ok(gst::FlowSuccess::Ok),
+        };
+
+        // Metadata frames have no timestamp, so this always ends up using the receive time
+        let (pts, _) = match self.calculate_timestamp(
+            element,
+            settings,
+            ndisys::NDIlib_recv_timestamp_undefined,
+            metadata_frame.timecode(),
+            gst::CLOCK_TIME_NONE,
+        ) {
+            Some(res) => res,
+            None => return Ok(gst::FlowSuccess::Ok),
+        };
+
+        let mut buffer = gst::Buffer::with_size(metadata.len()).unwrap();
+        {
+            let buffer = buffer.get_mut().unwrap();
+            buffer.set_pts(pts);
+            buffer
+                .map_writable()
+                .unwrap()
+                .as_mut_slice()
+                .copy_from_slice(metadata.as_bytes());
+        }
+
+        gst_log!(self.cat, obj: element, "Produced metadata buffer {:?}", buffer);
+
+        let caps = if self.metadata_pad.get_current_caps().is_none() {
+            Some(METADATA_CAPS.clone())
+        } else {
+            None
+        };
+
+        self.push_buffer(element, &self.metadata_pad, caps, buffer)
+    }
+
+    fn calculate_timestamp(
+        &self,
+        element: &gst::Element,
+        settings: &Settings,
+        timestamp: i64,
+        timecode: i64,
+        duration: gst::ClockTime,
+    ) -> Option<(gst::ClockTime, gst::ClockTime)> {
+        let observations = self.state.lock().unwrap().observations.clone();
+
+        calculate_timestamp(
+            self.cat,
+            element,
+            &observations,
+            settings.timestamp_mode,
+            timestamp,
+            timecode,
+            duration,
+        )
+    }
+
+    fn push_buffer(
+        &self,
+        element: &gst::Element,
+        pad: &gst::Pad,
+        caps: Option<gst::Caps>,
+        buffer: gst::Buffer,
+    ) -> Result<gst::FlowSuccess, gst::FlowError> {
+        self.configure_pad(element, pad, caps);
+
+        let res = pad.push(buffer);
+
+        let mut state = self.state.lock().unwrap();
+        state.flow_combiner.update_pad_flow(pad, res)
+    }
+
+    fn configure_pad(&self, element: &gst::Element, pad: &gst::Pad, caps: Option<gst::Caps>) {
+        let needs_stream_start = pad.get_current_caps().is_none();
+
+        if needs_stream_start {
+            let stream_id = pad
+                .create_stream_id(element, Some(pad.get_name().as_str()))
+                .unwrap();
+            pad.push_event(gst::Event::new_stream_start(&stream_id).build());
+        }
+
+        if let Some(caps) = caps {
+            gst_debug!(self.cat, obj: pad, "Configuring for caps {}", caps);
+            pad.push_event(gst::Event::new_caps(&caps).build());
+        }
+
+        if needs_stream_start {
+            let segment = gst::FormattedSegment::<gst::ClockTime>::new();
+            pad.push_event(gst::Event::new_segment(&segment).build());
+        }
+    }
+}
+
+pub fn register(plugin: &gst::Plugin) -> Result<(), glib::BoolError> {
+    gst::Element::register(
+        Some(plugin),
+        "ndisrc",
+        gst::Rank::None,
+        NdiSrc::get_type(),
+    )
+}
@@ -62,6 +62,47 @@ extern "C" {
         p_instance: NDIlib_recv_instance_t,
         p_total: *mut NDIlib_recv_queue_t,
     );
+    pub fn NDIlib_recv_ptz_is_supported(p_instance: NDIlib_recv_instance_t) -> bool;
+    pub fn NDIlib_recv_ptz_store_preset(
+        p_instance: NDIlib_recv_instance_t,
+        preset_no: ::std::os::raw::c_int,
+    ) -> bool;
+    pub fn NDIlib_recv_ptz_recall_preset(
+        p_instance: NDIlib_recv_instance_t,
+        preset_no: ::std::os::raw::c_int,
+        speed: f32,
+    ) -> bool;
+    pub fn NDIlib_recv_ptz_zoom(p_instance: NDIlib_recv_instance_t, zoom_value: f32) -> bool;
+    pub fn NDIlib_recv_ptz_zoom_speed(p_instance: NDIlib_recv_instance_t, zoom_speed: f32)
+        -> bool;
+    pub fn NDIlib_recv_ptz_pan_tilt(
+        p_instance: NDIlib_recv_instance_t,
+        pan_value: f32,
+        tilt_value: f32,
+    ) -> bool;
+    pub fn NDIlib_recv_ptz_pan_tilt_speed(
+        p_instance: NDIlib_recv_instance_t,
+        pan_speed: f32,
+        tilt_speed: f32,
+    ) -> bool;
+    pub fn NDIlib_recv_ptz_auto_focus(p_instance: NDIlib_recv_instance_t) -> bool;
+    pub fn NDIlib_recv_ptz_focus(p_instance: NDIlib_recv_instance_t, focus_value: f32) -> bool;
+    pub fn NDIlib_recv_ptz_focus_speed(p_instance: NDIlib_recv_instance_t, focus_speed: f32)
+        -> bool;
+    pub fn NDIlib_recv_ptz_white_balance_auto(p_instance: NDIlib_recv_instance_t) -> bool;
+    pub fn NDIlib_recv_ptz_white_balance_indoor(p_instance: NDIlib_recv_instance_t) -> bool;
+    pub fn NDIlib_recv_ptz_white_balance_outdoor(p_instance: NDIlib_recv_instance_t) -> bool;
+    pub fn NDIlib_recv_ptz_white_balance_oneshot(p_instance: NDIlib_recv_instance_t) -> bool;
+    pub fn NDIlib_recv_ptz_white_balance_manual(
+        p_instance: NDIlib_recv_instance_t,
+        red: f32,
+        blue: f32,
+    ) -> bool;
+    pub fn NDIlib_recv_ptz_exposure_auto(p_instance: NDIlib_recv_instance_t) -> bool;
+    pub fn NDIlib_recv_ptz_exposure_manual(
+        p_instance: NDIlib_recv_instance_t,
+        exposure_level: f32,
+    ) -> bool;
     pub fn NDIlib_send_create(
         p_create_settings: *const NDIlib_send_create_t
     ) -> NDIlib_send_instance_t;
@@ -72,6 +113,43 @@ extern "C" {
         p_instance: NDIlib_send_instance_t,
         p_video_data: *const NDIlib_video_frame_v2_t
     );
+    pub fn NDIlib_send_send_video_async_v2(
+        p_instance: NDIlib_send_instance_t,
+        p_video_data: *const NDIlib_video_frame_v2_t
+    );
+    pub fn NDIlib_send_send_audio_v2(
+        p_instance: NDIlib_send_instance_t,
+        p_audio_data: *const NDIlib_audio_frame_v2_t
+    );
+    pub fn NDIlib_send_send_metadata(
+        p_instance: NDIlib_send_instance_t,
+        p_metadata: *const NDIlib_metadata_frame_t
+    );
+    pub fn NDIlib_send_capture(
+        p_instance: NDIlib_send_instance_t,
+        p_metadata: *mut NDIlib_metadata_frame_t,
+        timeout_in_ms: u32
+    ) -> NDIlib_frame_type_e;
+    pub fn NDIlib_send_free_metadata(
+        p_instance: NDIlib_send_instance_t,
+        p_metadata: *const NDIlib_metadata_frame_t
+    );
+    pub fn NDIlib_send_clear_connection_metadata(
+        p_instance: NDIlib_send_instance_t
+    );
+    pub fn NDIlib_send_add_connection_metadata(
+        p_instance: NDIlib_send_instance_t,
+        p_metadata: *const NDIlib_metadata_frame_t
+    );
+    pub fn NDIlib_send_get_no_connections(
+        p_instance: NDIlib_send_instance_t,
+        timeout_in_ms: u32
+    ) -> ::std::os::raw::c_int;
+    pub fn NDIlib_send_get_tally(
+        p_instance: NDIlib_send_instance_t,
+        p_tally: *mut NDIlib_tally_t,
+        timeout_in_ms: u32
+    ) -> bool;
     pub fn NDIlib_util_send_send_audio_interleaved_16s(
         p_instance: NDIlib_send_instance_t,
         p_audio_data: *const NDIlib_audio_frame_interleaved_16s_t
@@ -234,6 +312,11 @@ extern "C" {
         p_src: *const NDIlib_audio_frame_interleaved_16s_t,
         p_dst: *mut NDIlib_audio_frame_v2_t,
     );
+
+    pub fn NDIlib_util_audio_to_interleaved_32f_v2(
+        p_src: *const NDIlib_audio_frame_v2_t,
+        p_dst: *mut NDIlib_audio_frame_interleaved_32f_t,
+    );
 }
 
 #[repr(C)]
@@ -246,3 +329,13 @@ pub struct NDIlib_audio_frame_interleaved_16s_t {
     pub reference_level: ::std::os::raw::c_int,
     pub p_data: *mut ::std::os::raw::c_short,
 }
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct NDIlib_audio_frame_interleaved_32f_t {
+    pub sample_rate: ::std::os::raw::c_int,
+    pub no_channels: ::std::os::raw::c_int,
+    pub no_samples: ::std::os::raw::c_int,
+    pub timecode: i64,
+    pub p_data: *mut ::std::os::raw::c_float,
+}
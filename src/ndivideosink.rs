@@ -5,29 +5,44 @@ use gst;
 use gst::prelude::*;
 use gst::subclass::prelude::*;
 use gst_base;
+use gst_base::prelude::*;
 use gst_base::subclass::prelude::*;
 use std::sync::Mutex;
-use std::i32;
+use std::{i32, u32};
 
-use crate::ndisys::*;
 use crate::send::*;
 
+use crate::TimecodeMode;
 use crate::DEFAULT_RECEIVER_NDI_NAME;
 
 #[derive(Debug)]
 struct Settings {
     ndi_name: String,
+    groups: Option<String>,
+    timecode_mode: TimecodeMode,
+    async_send: bool,
+    drop_without_connections: bool,
+    clock_video: bool,
+    wait_for_connection: u32,
+    connection_metadata: Option<String>,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Settings {
             ndi_name: DEFAULT_RECEIVER_NDI_NAME.clone(),
+            groups: None,
+            timecode_mode: TimecodeMode::Synthesize,
+            async_send: false,
+            drop_without_connections: false,
+            clock_video: false,
+            wait_for_connection: 0,
+            connection_metadata: None,
         }
     }
 }
 
-static PROPERTIES: [subclass::Property; 1] = [
+static PROPERTIES: [subclass::Property; 11] = [
     subclass::Property("ndi-name", |name| {
         glib::ParamSpec::string(
             name,
@@ -36,19 +51,115 @@ static PROPERTIES: [subclass::Property; 1] = [
             None,
             glib::ParamFlags::READWRITE,
         )
-    })
+    }),
+    subclass::Property("groups", |name| {
+        glib::ParamSpec::string(
+            name,
+            "Groups",
+            "Comma separated list of NDI groups to announce the stream in",
+            None,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("timecode-mode", |name| {
+        glib::ParamSpec::enum_(
+            name,
+            "Timecode Mode",
+            "Source of the NDI timecode of sent frames",
+            TimecodeMode::static_type(),
+            TimecodeMode::Synthesize as i32,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("async-send", |name| {
+        glib::ParamSpec::boolean(
+            name,
+            "Async Send",
+            "Send video frames asynchronously without copying them",
+            false,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("on-program", |name| {
+        glib::ParamSpec::boolean(
+            name,
+            "On Program",
+            "Whether the sent stream is on program of any receiver",
+            false,
+            glib::ParamFlags::READABLE,
+        )
+    }),
+    subclass::Property("on-preview", |name| {
+        glib::ParamSpec::boolean(
+            name,
+            "On Preview",
+            "Whether the sent stream is on preview of any receiver",
+            false,
+            glib::ParamFlags::READABLE,
+        )
+    }),
+    subclass::Property("connections", |name| {
+        glib::ParamSpec::int(
+            name,
+            "Connections",
+            "Number of receivers connected to the sender",
+            0,
+            i32::MAX,
+            0,
+            glib::ParamFlags::READABLE,
+        )
+    }),
+    subclass::Property("drop-without-connections", |name| {
+        glib::ParamSpec::boolean(
+            name,
+            "Drop Without Connections",
+            "Drop frames instead of sending them while no receiver is connected",
+            false,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("clock-video", |name| {
+        glib::ParamSpec::boolean(
+            name,
+            "Clock Video",
+            "Let the NDI SDK pace the output based on the video frames and disable sync",
+            false,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("wait-for-connection", |name| {
+        glib::ParamSpec::uint(
+            name,
+            "Wait For Connection",
+            "Time in ms to wait for a receiver to connect during preroll, 0 to not wait",
+            0,
+            u32::MAX,
+            0,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("connection-metadata", |name| {
+        glib::ParamSpec::string(
+            name,
+            "Connection Metadata",
+            "Additional XML metadata sent to every receiver when it connects",
+            None,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
 ];
 
 struct State {
     video_info: Option<gst_video::VideoInfo>,
-    sender: Option<SendInstance>,
+    // Value of the sync property before disabling it for clocking, restored when stopping
+    sync: Option<bool>,
 }
 
 impl Default for State {
     fn default() -> Self {
         Self {
-            sender: None,
-            video_info: None
+            video_info: None,
+            sync: None,
         }
     }
 }
@@ -57,6 +168,7 @@ pub(crate) struct NdiVideoSink {
     cat: gst::DebugCategory,
     settings: Mutex<Settings>,
     state: Mutex<State>,
+    sender: Sender,
 }
 
 impl ObjectSubclass for NdiVideoSink {
@@ -68,14 +180,17 @@ impl ObjectSubclass for NdiVideoSink {
     glib_object_subclass!();
 
     fn new() -> Self {
+        let cat = gst::DebugCategory::new(
+            "ndivideosink",
+            gst::DebugColorFlags::empty(),
+            Some("NewTek NDI Video Sink"),
+        );
+
         Self {
-            cat: gst::DebugCategory::new(
-                "ndivideosink",
-                gst::DebugColorFlags::empty(),
-                Some("NewTek NDI Video Sink"),
-            ),
+            cat,
             settings: Mutex::new(Default::default()),
             state: Mutex::new(Default::default()),
+            sender: Sender::new(cat),
         }
     }
 
@@ -101,6 +216,7 @@ impl ObjectSubclass for NdiVideoSink {
                         &gst_video::VideoFormat::Bgrx.to_string(),
                         &gst_video::VideoFormat::Rgba.to_string(),
                         &gst_video::VideoFormat::Rgbx.to_string(),
+                        &gst_video::VideoFormat::Ayuv.to_string(),
                     ]),
                 ),
                 ("width", &gst::IntRange::<i32>::new(0, i32::MAX)),
@@ -115,6 +231,23 @@ impl ObjectSubclass for NdiVideoSink {
             ],
         );
 
+        #[cfg(feature = "interlaced-fields")]
+        let caps = {
+            let mut tmp = caps.copy();
+            {
+                let tmp = tmp.get_mut().unwrap();
+                tmp.set_features_simple(Some(gst::CapsFeatures::new(&["format:Interlaced"])));
+            }
+
+            let mut caps = caps;
+            {
+                let caps = caps.get_mut().unwrap();
+                caps.append(tmp);
+            }
+
+            caps
+        };
+
         let sink_pad_template = gst::PadTemplate::new(
             "sink",
             gst::PadDirection::Sink,
@@ -125,14 +258,43 @@ impl ObjectSubclass for NdiVideoSink {
 
         klass.add_pad_template(sink_pad_template);
         klass.install_properties(&PROPERTIES);
+
+        klass.add_signal(
+            "tally-changed",
+            glib::SignalFlags::RUN_LAST,
+            &[bool::static_type(), bool::static_type()],
+            glib::Type::Unit,
+        );
+
+        klass.add_signal(
+            "metadata-received",
+            glib::SignalFlags::RUN_LAST,
+            &[String::static_type()],
+            glib::Type::Unit,
+        );
+
+        klass.add_action_signal(
+            "send-metadata",
+            &[String::static_type()],
+            bool::static_type(),
+            |args| {
+                let element = args[0].get::<gst::Element>().unwrap();
+                let data = args[1].get::<String>().unwrap_or_default();
+                let imp = Self::from_instance(&element);
+
+                Some(imp.sender.send_metadata(&element, &data).to_value())
+            },
+        );
     }
 }
 
 impl ObjectImpl for NdiVideoSink {
     glib_object_impl!();
 
-    fn set_property(&self, _obj: &glib::Object, id: usize, value: &glib::Value) {
+    fn set_property(&self, obj: &glib::Object, id: usize, value: &glib::Value) {
         let prop = &PROPERTIES[id];
+        let element = obj.downcast_ref::<gst::Element>().unwrap();
+
         match *prop {
             subclass::Property("ndi-name", ..) => {
                 let mut settings = self.settings.lock().unwrap();
@@ -142,6 +304,97 @@ impl ObjectImpl for NdiVideoSink {
                 
                 settings.ndi_name = ndi_name;
             }
+            subclass::Property("groups", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let groups = value.get();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing groups from {:?} to {:?}",
+                    settings.groups,
+                    groups,
+                );
+                settings.groups = groups;
+            }
+            subclass::Property("timecode-mode", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let timecode_mode = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing timecode mode from {:?} to {:?}",
+                    settings.timecode_mode,
+                    timecode_mode
+                );
+                settings.timecode_mode = timecode_mode;
+            }
+            subclass::Property("async-send", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let async_send = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing async send from {} to {}",
+                    settings.async_send,
+                    async_send
+                );
+                settings.async_send = async_send;
+            }
+            subclass::Property("drop-without-connections", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let drop_without_connections = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing drop without connections from {} to {}",
+                    settings.drop_without_connections,
+                    drop_without_connections
+                );
+                settings.drop_without_connections = drop_without_connections;
+            }
+            subclass::Property("clock-video", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let clock_video = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing clock-video from {} to {}",
+                    settings.clock_video,
+                    clock_video
+                );
+                settings.clock_video = clock_video;
+            }
+            subclass::Property("wait-for-connection", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let wait_for_connection = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: element,
+                    "Changing wait for connection from {} to {}",
+                    settings.wait_for_connection,
+                    wait_for_connection
+                );
+                settings.wait_for_connection = wait_for_connection;
+            }
+            subclass::Property("connection-metadata", ..) => {
+                let connection_metadata: Option<String> = value.get();
+                {
+                    let mut settings = self.settings.lock().unwrap();
+                    gst_debug!(
+                        self.cat,
+                        obj: element,
+                        "Changing connection metadata from {:?} to {:?}",
+                        settings.connection_metadata,
+                        connection_metadata
+                    );
+                    settings.connection_metadata = connection_metadata.clone();
+                }
+
+                self.sender.set_connection_metadata(
+                    element,
+                    connection_metadata.as_ref().map(String::as_str),
+                );
+            }
             _ => unimplemented!(),
         }
     }
@@ -154,6 +407,37 @@ impl ObjectImpl for NdiVideoSink {
                 let settings = self.settings.lock().unwrap();
                 Ok(settings.ndi_name.to_value())
             }
+            subclass::Property("groups", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.groups.to_value())
+            }
+            subclass::Property("timecode-mode", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.timecode_mode.to_value())
+            }
+            subclass::Property("async-send", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.async_send.to_value())
+            }
+            subclass::Property("on-program", ..) => Ok(self.sender.on_program().to_value()),
+            subclass::Property("on-preview", ..) => Ok(self.sender.on_preview().to_value()),
+            subclass::Property("connections", ..) => Ok(self.sender.connections().to_value()),
+            subclass::Property("drop-without-connections", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.drop_without_connections.to_value())
+            }
+            subclass::Property("clock-video", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.clock_video.to_value())
+            }
+            subclass::Property("wait-for-connection", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.wait_for_connection.to_value())
+            }
+            subclass::Property("connection-metadata", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.connection_metadata.to_value())
+            }
             _ => unimplemented!(),
         }
     }
@@ -162,52 +446,150 @@ impl ObjectImpl for NdiVideoSink {
 impl ElementImpl for NdiVideoSink {}
 
 impl BaseSinkImpl for NdiVideoSink {
-    fn start(&self, _element: &gst_base::BaseSink) -> Result<(), gst::ErrorMessage> {
-        let mut state = self.state.lock().unwrap();
+    fn start(&self, element: &gst_base::BaseSink) -> Result<(), gst::ErrorMessage> {
         let settings = self.settings.lock().unwrap();
 
+        let sender = create_send_instance(
+            settings.ndi_name.clone(),
+            settings.groups.as_ref().map(String::as_str),
+            settings.clock_video,
+            false,
+        )
+        .map_err(|err| {
+            gst_error_msg!(
+                gst::ResourceError::OpenWrite,
+                ["Failed to create NDI sender '{}': {}", settings.ndi_name, err]
+            )
+        })?;
 
-        let sender = create_send_instance(settings.ndi_name.clone(), false, false);
-        state.sender = Some(sender.unwrap());
+        self.sender.start(
+            element.upcast_ref(),
+            sender,
+            settings.connection_metadata.as_ref().map(String::as_str),
+        );
+        let clock_video = settings.clock_video;
+        drop(settings);
+
+        // The SDK blocks in the send calls when clocking, so also waiting for the
+        // pipeline clock would pace the output twice
+        if clock_video {
+            gst_debug!(self.cat, obj: element, "Disabling sync as the NDI SDK is clocking");
+            self.state.lock().unwrap().sync = Some(element.get_sync());
+            element.set_sync(false);
+        }
 
         Ok(())
     }
 
+    fn stop(&self, element: &gst_base::BaseSink) -> Result<(), gst::ErrorMessage> {
+        let sync = self.state.lock().unwrap().sync.take();
+        if let Some(sync) = sync {
+            element.set_sync(sync);
+        }
+
+        *self.state.lock().unwrap() = State::default();
+        self.sender.stop();
+        Ok(())
+    }
+
     fn set_caps(&self, _element: &gst_base::BaseSink, caps: &gst::Caps) -> Result<(), gst::LoggableError> {
         let mut state = self.state.lock().unwrap();
         state.video_info = gst_video::VideoInfo::from_caps(caps);
         Ok(())
     }
 
+    fn preroll(
+        &self,
+        element: &gst_base::BaseSink,
+        _buffer: &gst::Buffer,
+    ) -> Result<gst::FlowSuccess, gst::FlowError> {
+        let wait_for_connection = self.settings.lock().unwrap().wait_for_connection;
+        if wait_for_connection > 0 {
+            self.sender
+                .wait_for_connection(element.upcast_ref(), wait_for_connection)?;
+        }
+
+        Ok(gst::FlowSuccess::Ok)
+    }
+
+    fn unlock(&self, element: &gst_base::BaseSink) -> Result<(), gst::ErrorMessage> {
+        gst_debug!(self.cat, obj: element, "Unlocking");
+        self.sender.unlock();
+        Ok(())
+    }
+
+    fn unlock_stop(&self, element: &gst_base::BaseSink) -> Result<(), gst::ErrorMessage> {
+        gst_debug!(self.cat, obj: element, "Stop unlocking");
+        self.sender.unlock_stop();
+        Ok(())
+    }
+
     fn render(&self, element: &gst_base::BaseSink, buffer: &gst::Buffer,
     ) -> Result<gst::FlowSuccess, gst::FlowError> {
+        let drop_without_connections = self.settings.lock().unwrap().drop_without_connections;
+        if self
+            .sender
+            .should_drop(element.upcast_ref(), drop_without_connections, buffer)
+        {
+            return Ok(gst::FlowSuccess::Ok);
+        }
+
         let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
 
         gst_trace!(self.cat, obj: element, "Rendering {:?}", buffer);
-        let map = buffer.map_readable().ok_or_else(|| {
-            gst_element_error!(element, gst::CoreError::Failed, ["Failed to map buffer"]);
-            gst::FlowError::Error
-        })?;
 
         if let Some(ref video_info) = state.video_info {
-            //TODO: find better way to get stride out
-            let in_frame = gst_video::VideoFrameRef::from_buffer_ref_readable(buffer.as_ref(), &video_info).unwrap();
+            let (timecode_mode, async_send) = {
+                let settings = self.settings.lock().unwrap();
+                (settings.timecode_mode, settings.async_send)
+            };
+            let segment = element
+                .get_segment()
+                .downcast::<gst::ClockTime>()
+                .unwrap_or_else(|_| gst::FormattedSegment::new());
+            let timecode = get_timecode(timecode_mode, &segment, buffer.as_ref());
+
+            let frame = if async_send {
+                create_ndi_send_video_frame_from_owned_buffer(video_info, buffer.clone(), timecode)
+            } else {
+                create_ndi_send_video_frame_from_buffer(video_info, buffer.as_ref(), timecode)
+            }
+            .map_err(|err| {
+                gst_element_error!(
+                    element,
+                    gst::StreamError::Format,
+                    ["Failed to create NDI video frame: {}", err]
+                );
+                gst::FlowError::Error
+            })?;
+
+            self.sender.with_instance(|sender| {
+                if async_send {
+                    sender.send_video_async(frame);
+                } else {
+                    sender.flush_async();
+                    sender.send_video(frame);
+                }
+            });
+        }
 
-            let frame = create_ndi_send_video_frame(
-                video_info.width() as i32,
-                video_info.height() as i32,
-                NDIlib_frame_format_type_e::NDIlib_frame_format_type_progressive,
-            )
-            .with_format(video_info.format())
-            .with_data(map.as_ref().to_vec(), in_frame.plane_stride()[0] as i32)
-            .build();
+        Ok(gst::FlowSuccess::Ok)
+    }
 
-            if let Some(ref mut sender) = state.sender {
-                sender.send_video(frame.unwrap());
+    fn event(&self, element: &gst_base::BaseSink, event: gst::Event) -> bool {
+        match event.view() {
+            gst::EventView::FlushStart(_) | gst::EventView::Eos(_) => {
+                self.sender.with_instance(SendInstance::flush_async);
             }
+            _ => (),
         }
 
-        Ok(gst::FlowSuccess::Ok)
+        if let Some(data) = get_metadata_from_event(&event) {
+            return self.sender.send_metadata(element.upcast_ref(), &data);
+        }
+
+        self.parent_event(element, event)
     }
 }
 
@@ -14,10 +14,15 @@ use std::{i32, u32};
 use crate::ndisys;
 
 use crate::connect_ndi;
+use crate::get_recv_color_format;
+use crate::video_meta_supported_downstream;
 
 use crate::Receiver;
 use crate::ReceiverControlHandle;
 use crate::ReceiverItem;
+use crate::RecvColorFormat;
+use crate::RecvInstance;
+use crate::TimeoutAction;
 use crate::TimestampMode;
 use crate::VideoReceiver;
 use crate::DEFAULT_RECEIVER_NDI_NAME;
@@ -26,11 +31,18 @@ use crate::DEFAULT_RECEIVER_NDI_NAME;
 struct Settings {
     ndi_name: Option<String>,
     ip_address: Option<String>,
+    groups: Option<String>,
+    extra_ips: Option<String>,
+    show_local_sources: bool,
     connect_timeout: u32,
     timeout: u32,
+    timeout_action: TimeoutAction,
     receiver_ndi_name: String,
     bandwidth: ndisys::NDIlib_recv_bandwidth_e,
+    color_format: RecvColorFormat,
     timestamp_mode: TimestampMode,
+    on_program: bool,
+    on_preview: bool,
 }
 
 impl Default for Settings {
@@ -38,16 +50,23 @@ impl Default for Settings {
         Settings {
             ndi_name: None,
             ip_address: None,
+            groups: None,
+            extra_ips: None,
+            show_local_sources: true,
             receiver_ndi_name: DEFAULT_RECEIVER_NDI_NAME.clone(),
             connect_timeout: 10000,
             timeout: 5000,
+            timeout_action: TimeoutAction::Eos,
             bandwidth: ndisys::NDIlib_recv_bandwidth_highest,
+            color_format: RecvColorFormat::Auto,
             timestamp_mode: TimestampMode::ReceiveTime,
+            on_program: false,
+            on_preview: false,
         }
     }
 }
 
-static PROPERTIES: [subclass::Property; 7] = [
+static PROPERTIES: [subclass::Property; 15] = [
     subclass::Property("ndi-name", |name| {
         glib::ParamSpec::string(
             name,
@@ -66,6 +85,33 @@ static PROPERTIES: [subclass::Property; 7] = [
             glib::ParamFlags::READWRITE,
         )
     }),
+    subclass::Property("groups", |name| {
+        glib::ParamSpec::string(
+            name,
+            "Groups",
+            "Comma separated list of NDI groups to search for the sender in",
+            None,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("extra-ips", |name| {
+        glib::ParamSpec::string(
+            name,
+            "Extra IPs",
+            "Comma separated list of additional IP addresses to search for the sender on",
+            None,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("show-local-sources", |name| {
+        glib::ParamSpec::boolean(
+            name,
+            "Show Local Sources",
+            "Whether to consider senders running on this machine",
+            true,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
     subclass::Property("receiver-ndi-name", |name| {
         glib::ParamSpec::string(
             name,
@@ -97,6 +143,16 @@ static PROPERTIES: [subclass::Property; 7] = [
             glib::ParamFlags::READWRITE,
         )
     }),
+    subclass::Property("timeout-action", |name| {
+        glib::ParamSpec::enum_(
+            name,
+            "Timeout Action",
+            "What to do if no frames are received for the receive timeout",
+            TimeoutAction::static_type(),
+            TimeoutAction::Eos as i32,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
     subclass::Property("bandwidth", |name| {
         glib::ParamSpec::int(
             name,
@@ -108,6 +164,16 @@ static PROPERTIES: [subclass::Property; 7] = [
             glib::ParamFlags::READWRITE,
         )
     }),
+    subclass::Property("color-format", |name| {
+        glib::ParamSpec::enum_(
+            name,
+            "Color Format",
+            "Receive color format, auto selects one based on the downstream caps",
+            RecvColorFormat::static_type(),
+            RecvColorFormat::Auto as i32,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
     subclass::Property("timestamp-mode", |name| {
         glib::ParamSpec::enum_(
             name,
@@ -118,6 +184,33 @@ static PROPERTIES: [subclass::Property; 7] = [
             glib::ParamFlags::READWRITE,
         )
     }),
+    subclass::Property("on-program", |name| {
+        glib::ParamSpec::boolean(
+            name,
+            "On Program",
+            "Tell the sender that this source is on program",
+            false,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("on-preview", |name| {
+        glib::ParamSpec::boolean(
+            name,
+            "On Preview",
+            "Tell the sender that this source is on preview",
+            false,
+            glib::ParamFlags::READWRITE,
+        )
+    }),
+    subclass::Property("ptz-supported", |name| {
+        glib::ParamSpec::boolean(
+            name,
+            "PTZ Supported",
+            "Whether the sender is a PTZ camera that can be controlled with the ptz-* signals",
+            false,
+            glib::ParamFlags::READABLE,
+        )
+    }),
 ];
 
 struct State {
@@ -188,6 +281,7 @@ impl ObjectSubclass for NdiVideoSrc {
                         &gst_video::VideoFormat::Bgrx.to_string(),
                         &gst_video::VideoFormat::Rgba.to_string(),
                         &gst_video::VideoFormat::Rgbx.to_string(),
+                        &gst_video::VideoFormat::Ayuv.to_string(),
                     ]),
                 ),
                 ("width", &gst::IntRange::<i32>::new(0, i32::MAX)),
@@ -229,6 +323,273 @@ impl ObjectSubclass for NdiVideoSrc {
         klass.add_pad_template(src_pad_template);
 
         klass.install_properties(&PROPERTIES);
+
+        // Action signals for controlling PTZ cameras, see the ptz-supported property. They
+        // return false if not connected or if the sender did not accept the command.
+
+        klass.add_action_signal(
+            "ptz-zoom",
+            &[f32::static_type()],
+            bool::static_type(),
+            |args| {
+                let element = args[0].get::<gst_base::BaseSrc>().unwrap();
+                let zoom = args[1].get::<f32>().unwrap_or_default();
+                let src = Self::from_instance(&element);
+
+                let res = src.ptz(&element, "zoom", |recv| recv.ptz_zoom(zoom));
+
+                Some(res.to_value())
+            },
+        );
+
+        klass.add_action_signal(
+            "ptz-zoom-speed",
+            &[f32::static_type()],
+            bool::static_type(),
+            |args| {
+                let element = args[0].get::<gst_base::BaseSrc>().unwrap();
+                let zoom_speed = args[1].get::<f32>().unwrap_or_default();
+                let src = Self::from_instance(&element);
+
+                let res = src.ptz(&element, "zoom-speed", |recv| recv.ptz_zoom_speed(zoom_speed));
+
+                Some(res.to_value())
+            },
+        );
+
+        klass.add_action_signal(
+            "ptz-pan-tilt",
+            &[f32::static_type(), f32::static_type()],
+            bool::static_type(),
+            |args| {
+                let element = args[0].get::<gst_base::BaseSrc>().unwrap();
+                let pan = args[1].get::<f32>().unwrap_or_default();
+                let tilt = args[2].get::<f32>().unwrap_or_default();
+                let src = Self::from_instance(&element);
+
+                let res = src.ptz(&element, "pan-tilt", |recv| recv.ptz_pan_tilt(pan, tilt));
+
+                Some(res.to_value())
+            },
+        );
+
+        klass.add_action_signal(
+            "ptz-pan-tilt-speed",
+            &[f32::static_type(), f32::static_type()],
+            bool::static_type(),
+            |args| {
+                let element = args[0].get::<gst_base::BaseSrc>().unwrap();
+                let pan_speed = args[1].get::<f32>().unwrap_or_default();
+                let tilt_speed = args[2].get::<f32>().unwrap_or_default();
+                let src = Self::from_instance(&element);
+
+                let res = src.ptz(&element, "pan-tilt-speed", |recv| {
+                    recv.ptz_pan_tilt_speed(pan_speed, tilt_speed)
+                });
+
+                Some(res.to_value())
+            },
+        );
+
+        klass.add_action_signal(
+            "ptz-auto-focus",
+            &[],
+            bool::static_type(),
+            |args| {
+                let element = args[0].get::<gst_base::BaseSrc>().unwrap();
+                let src = Self::from_instance(&element);
+
+                let res = src.ptz(&element, "auto-focus", RecvInstance::ptz_auto_focus);
+
+                Some(res.to_value())
+            },
+        );
+
+        klass.add_action_signal(
+            "ptz-focus",
+            &[f32::static_type()],
+            bool::static_type(),
+            |args| {
+                let element = args[0].get::<gst_base::BaseSrc>().unwrap();
+                let focus = args[1].get::<f32>().unwrap_or_default();
+                let src = Self::from_instance(&element);
+
+                let res = src.ptz(&element, "focus", |recv| recv.ptz_focus(focus));
+
+                Some(res.to_value())
+            },
+        );
+
+        klass.add_action_signal(
+            "ptz-focus-speed",
+            &[f32::static_type()],
+            bool::static_type(),
+            |args| {
+                let element = args[0].get::<gst_base::BaseSrc>().unwrap();
+                let focus_speed = args[1].get::<f32>().unwrap_or_default();
+                let src = Self::from_instance(&element);
+
+                let res = src.ptz(&element, "focus-speed", |recv| {
+                    recv.ptz_focus_speed(focus_speed)
+                });
+
+                Some(res.to_value())
+            },
+        );
+
+        klass.add_action_signal(
+            "ptz-white-balance-auto",
+            &[],
+            bool::static_type(),
+            |args| {
+                let element = args[0].get::<gst_base::BaseSrc>().unwrap();
+                let src = Self::from_instance(&element);
+
+                let res = src.ptz(
+                    &element,
+                    "white-balance-auto",
+                    RecvInstance::ptz_white_balance_auto,
+                );
+
+                Some(res.to_value())
+            },
+        );
+
+        klass.add_action_signal(
+            "ptz-white-balance-indoor",
+            &[],
+            bool::static_type(),
+            |args| {
+                let element = args[0].get::<gst_base::BaseSrc>().unwrap();
+                let src = Self::from_instance(&element);
+
+                let res = src.ptz(
+                    &element,
+                    "white-balance-indoor",
+                    RecvInstance::ptz_white_balance_indoor,
+                );
+
+                Some(res.to_value())
+            },
+        );
+
+        klass.add_action_signal(
+            "ptz-white-balance-outdoor",
+            &[],
+            bool::static_type(),
+            |args| {
+                let element = args[0].get::<gst_base::BaseSrc>().unwrap();
+                let src = Self::from_instance(&element);
+
+                let res = src.ptz(
+                    &element,
+                    "white-balance-outdoor",
+                    RecvInstance::ptz_white_balance_outdoor,
+                );
+
+                Some(res.to_value())
+            },
+        );
+
+        klass.add_action_signal(
+            "ptz-white-balance-oneshot",
+            &[],
+            bool::static_type(),
+            |args| {
+                let element = args[0].get::<gst_base::BaseSrc>().unwrap();
+                let src = Self::from_instance(&element);
+
+                let res = src.ptz(
+                    &element,
+                    "white-balance-oneshot",
+                    RecvInstance::ptz_white_balance_oneshot,
+                );
+
+                Some(res.to_value())
+            },
+        );
+
+        klass.add_action_signal(
+            "ptz-white-balance-manual",
+            &[f32::static_type(), f32::static_type()],
+            bool::static_type(),
+            |args| {
+                let element = args[0].get::<gst_base::BaseSrc>().unwrap();
+                let red = args[1].get::<f32>().unwrap_or_default();
+                let blue = args[2].get::<f32>().unwrap_or_default();
+                let src = Self::from_instance(&element);
+
+                let res = src.ptz(&element, "white-balance-manual", |recv| {
+                    recv.ptz_white_balance_manual(red, blue)
+                });
+
+                Some(res.to_value())
+            },
+        );
+
+        klass.add_action_signal(
+            "ptz-exposure-auto",
+            &[],
+            bool::static_type(),
+            |args| {
+                let element = args[0].get::<gst_base::BaseSrc>().unwrap();
+                let src = Self::from_instance(&element);
+
+                let res = src.ptz(&element, "exposure-auto", RecvInstance::ptz_exposure_auto);
+
+                Some(res.to_value())
+            },
+        );
+
+        klass.add_action_signal(
+            "ptz-exposure-manual",
+            &[f32::static_type()],
+            bool::static_type(),
+            |args| {
+                let element = args[0].get::<gst_base::BaseSrc>().unwrap();
+                let exposure = args[1].get::<f32>().unwrap_or_default();
+                let src = Self::from_instance(&element);
+
+                let res = src.ptz(&element, "exposure-manual", |recv| {
+                    recv.ptz_exposure_manual(exposure)
+                });
+
+                Some(res.to_value())
+            },
+        );
+
+        klass.add_action_signal(
+            "ptz-store-preset",
+            &[i32::static_type()],
+            bool::static_type(),
+            |args| {
+                let element = args[0].get::<gst_base::BaseSrc>().unwrap();
+                let preset = args[1].get::<i32>().unwrap_or_default();
+                let src = Self::from_instance(&element);
+
+                let res = src.ptz(&element, "store-preset", |recv| recv.ptz_store_preset(preset));
+
+                Some(res.to_value())
+            },
+        );
+
+        klass.add_action_signal(
+            "ptz-recall-preset",
+            &[i32::static_type(), f32::static_type()],
+            bool::static_type(),
+            |args| {
+                let element = args[0].get::<gst_base::BaseSrc>().unwrap();
+                let preset = args[1].get::<i32>().unwrap_or_default();
+                let speed = args[2].get::<f32>().unwrap_or_default();
+                let src = Self::from_instance(&element);
+
+                let res = src.ptz(&element, "recall-preset", |recv| {
+                    recv.ptz_recall_preset(preset, speed)
+                });
+
+                Some(res.to_value())
+            },
+        );
     }
 }
 
@@ -274,6 +635,42 @@ impl ObjectImpl for NdiVideoSrc {
                 );
                 settings.ip_address = ip_address;
             }
+            subclass::Property("groups", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let groups = value.get();
+                gst_debug!(
+                    self.cat,
+                    obj: basesrc,
+                    "Changing groups from {:?} to {:?}",
+                    settings.groups,
+                    groups,
+                );
+                settings.groups = groups;
+            }
+            subclass::Property("extra-ips", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let extra_ips = value.get();
+                gst_debug!(
+                    self.cat,
+                    obj: basesrc,
+                    "Changing extra-ips from {:?} to {:?}",
+                    settings.extra_ips,
+                    extra_ips,
+                );
+                settings.extra_ips = extra_ips;
+            }
+            subclass::Property("show-local-sources", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let show_local_sources = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: basesrc,
+                    "Changing show-local-sources from {} to {}",
+                    settings.show_local_sources,
+                    show_local_sources,
+                );
+                settings.show_local_sources = show_local_sources;
+            }
             subclass::Property("receiver-ndi-name", ..) => {
                 let mut settings = self.settings.lock().unwrap();
                 let receiver_ndi_name = value.get();
@@ -311,6 +708,18 @@ impl ObjectImpl for NdiVideoSrc {
                 );
                 settings.timeout = timeout;
             }
+            subclass::Property("timeout-action", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let timeout_action = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: basesrc,
+                    "Changing timeout action from {:?} to {:?}",
+                    settings.timeout_action,
+                    timeout_action,
+                );
+                settings.timeout_action = timeout_action;
+            }
             subclass::Property("bandwidth", ..) => {
                 let mut settings = self.settings.lock().unwrap();
                 let bandwidth = value.get().unwrap();
@@ -323,6 +732,18 @@ impl ObjectImpl for NdiVideoSrc {
                 );
                 settings.bandwidth = bandwidth;
             }
+            subclass::Property("color-format", ..) => {
+                let mut settings = self.settings.lock().unwrap();
+                let color_format = value.get().unwrap();
+                gst_debug!(
+                    self.cat,
+                    obj: basesrc,
+                    "Changing color format from {:?} to {:?}",
+                    settings.color_format,
+                    color_format,
+                );
+                settings.color_format = color_format;
+            }
             subclass::Property("timestamp-mode", ..) => {
                 let mut settings = self.settings.lock().unwrap();
                 let timestamp_mode = value.get().unwrap();
@@ -339,6 +760,44 @@ impl ObjectImpl for NdiVideoSrc {
                 }
                 settings.timestamp_mode = timestamp_mode;
             }
+            subclass::Property("on-program", ..) => {
+                let (on_program, on_preview) = {
+                    let mut settings = self.settings.lock().unwrap();
+                    let on_program = value.get().unwrap();
+                    gst_debug!(
+                        self.cat,
+                        obj: basesrc,
+                        "Changing on-program from {} to {}",
+                        settings.on_program,
+                        on_program
+                    );
+                    settings.on_program = on_program;
+                    (settings.on_program, settings.on_preview)
+                };
+
+                if let Some(ref controller) = *self.receiver_controller.lock().unwrap() {
+                    controller.set_tally(on_program, on_preview);
+                }
+            }
+            subclass::Property("on-preview", ..) => {
+                let (on_program, on_preview) = {
+                    let mut settings = self.settings.lock().unwrap();
+                    let on_preview = value.get().unwrap();
+                    gst_debug!(
+                        self.cat,
+                        obj: basesrc,
+                        "Changing on-preview from {} to {}",
+                        settings.on_preview,
+                        on_preview
+                    );
+                    settings.on_preview = on_preview;
+                    (settings.on_program, settings.on_preview)
+                };
+
+                if let Some(ref controller) = *self.receiver_controller.lock().unwrap() {
+                    controller.set_tally(on_program, on_preview);
+                }
+            }
             _ => unimplemented!(),
         }
     }
@@ -355,6 +814,18 @@ impl ObjectImpl for NdiVideoSrc {
                 let settings = self.settings.lock().unwrap();
                 Ok(settings.ip_address.to_value())
             }
+            subclass::Property("groups", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.groups.to_value())
+            }
+            subclass::Property("extra-ips", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.extra_ips.to_value())
+            }
+            subclass::Property("show-local-sources", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.show_local_sources.to_value())
+            }
             subclass::Property("receiver-ndi-name", ..) => {
                 let settings = self.settings.lock().unwrap();
                 Ok(settings.receiver_ndi_name.to_value())
@@ -367,14 +838,39 @@ impl ObjectImpl for NdiVideoSrc {
                 let settings = self.settings.lock().unwrap();
                 Ok(settings.timeout.to_value())
             }
+            subclass::Property("timeout-action", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.timeout_action.to_value())
+            }
             subclass::Property("bandwidth", ..) => {
                 let settings = self.settings.lock().unwrap();
                 Ok(settings.bandwidth.to_value())
             }
+            subclass::Property("color-format", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.color_format.to_value())
+            }
             subclass::Property("timestamp-mode", ..) => {
                 let settings = self.settings.lock().unwrap();
                 Ok(settings.timestamp_mode.to_value())
             }
+            subclass::Property("on-program", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.on_program.to_value())
+            }
+            subclass::Property("on-preview", ..) => {
+                let settings = self.settings.lock().unwrap();
+                Ok(settings.on_preview.to_value())
+            }
+            subclass::Property("ptz-supported", ..) => {
+                let receiver_controller = self.receiver_controller.lock().unwrap();
+                let ptz_supported = receiver_controller
+                    .as_ref()
+                    .and_then(ReceiverControlHandle::recv_instance)
+                    .map(|recv| recv.ptz_is_supported())
+                    .unwrap_or(false);
+                Ok(ptz_supported.to_value())
+            }
             _ => unimplemented!(),
         }
     }
@@ -412,7 +908,8 @@ impl ElementImpl for NdiVideoSrc {
 impl BaseSrcImpl for NdiVideoSrc {
     fn negotiate(&self, _element: &gst_base::BaseSrc) -> Result<(), gst::LoggableError> {
         // Always succeed here without doing anything: we will set the caps once we received a
-        // buffer, there's nothing we can negotiate
+        // buffer, and the color format was already selected based on the downstream caps when
+        // connecting
         Ok(())
     }
 
@@ -443,16 +940,25 @@ impl BaseSrcImpl for NdiVideoSrc {
             ));
         }
 
+        let color_format =
+            get_recv_color_format(self.cat, &element.get_src_pad(), settings.color_format);
+
         let receiver = connect_ndi(
             self.cat,
             element,
             settings.ip_address.as_ref().map(String::as_str),
             settings.ndi_name.as_ref().map(String::as_str),
             &settings.receiver_ndi_name,
+            settings.groups.as_ref().map(String::as_str),
+            settings.extra_ips.as_ref().map(String::as_str),
+            settings.show_local_sources,
             settings.connect_timeout,
             settings.bandwidth,
+            color_format,
             settings.timestamp_mode,
             settings.timeout,
+            settings.timeout_action,
+            0,
         );
 
         // settings.id_receiver exists
@@ -462,8 +968,16 @@ impl BaseSrcImpl for NdiVideoSrc {
                 ["Could not connect to this source"]
             )),
             Some(receiver) => {
-                *self.receiver_controller.lock().unwrap() =
-                    Some(receiver.receiver_control_handle());
+                // Take the tally from the current settings while holding the controller lock
+                // so that no change from the properties is missed
+                let mut receiver_controller = self.receiver_controller.lock().unwrap();
+                let controller = receiver.receiver_control_handle();
+                {
+                    let settings = self.settings.lock().unwrap();
+                    controller.set_tally(settings.on_program, settings.on_preview);
+                }
+                *receiver_controller = Some(controller);
+                drop(receiver_controller);
                 let mut state = self.state.lock().unwrap();
                 state.receiver = Some(receiver);
 
@@ -552,43 +1066,111 @@ impl BaseSrcImpl for NdiVideoSrc {
             }
         };
 
-        match recv.capture() {
-            ReceiverItem::Buffer(buffer, info) => {
-                let mut state = self.state.lock().unwrap();
-                state.receiver = Some(recv);
-                if state.info.as_ref() != Some(&info) {
-                    let caps = info.to_caps().ok_or_else(|| {
-                        gst_element_error!(
-                            element,
-                            gst::ResourceError::Settings,
-                            ["Invalid audio info received: {:?}", info]
+        loop {
+            match recv.capture() {
+                ReceiverItem::Buffer(buffer, info) => {
+                    let mut state = self.state.lock().unwrap();
+                    state.receiver = Some(recv);
+                    if state.info.as_ref() != Some(&info) {
+                        let caps = info.to_caps().ok_or_else(|| {
+                            gst_element_error!(
+                                element,
+                                gst::ResourceError::Settings,
+                                ["Invalid audio info received: {:?}", info]
+                            );
+                            gst::FlowError::NotNegotiated
+                        })?;
+                        state.info = Some(info.clone());
+                        state.current_latency = buffer.get_duration();
+                        drop(state);
+                        gst_debug!(self.cat, obj: element, "Configuring for caps {}", caps);
+                        element.set_caps(&caps).map_err(|_| {
+                            gst_element_error!(
+                                element,
+                                gst::CoreError::Negotiation,
+                                ["Failed to negotiate caps: {:?}", caps]
+                            );
+                            gst::FlowError::NotNegotiated
+                        })?;
+
+                        let video_meta_supported = video_meta_supported_downstream(
+                            &element.get_static_pad("src").unwrap(),
+                            &caps,
                         );
-                        gst::FlowError::NotNegotiated
-                    })?;
-                    state.info = Some(info.clone());
-                    state.current_latency = buffer.get_duration();
-                    drop(state);
-                    gst_debug!(self.cat, obj: element, "Configuring for caps {}", caps);
-                    element.set_caps(&caps).map_err(|_| {
-                        gst_element_error!(
-                            element,
-                            gst::CoreError::Negotiation,
-                            ["Failed to negotiate caps: {:?}", caps]
+                        gst_debug!(
+                            self.cat,
+                            obj: element,
+                            "Downstream supports video meta: {}",
+                            video_meta_supported
                         );
-                        gst::FlowError::NotNegotiated
-                    })?;
+                        if let Some(ref receiver) = self.state.lock().unwrap().receiver {
+                            receiver.set_video_meta_supported(&info, video_meta_supported);
+                        }
+
+                        let _ = element
+                            .post_message(&gst::Message::new_latency().src(Some(element)).build());
+                    }
 
-                    let _ = element
-                        .post_message(&gst::Message::new_latency().src(Some(element)).build());
+                    return Ok(buffer);
                 }
+                ReceiverItem::Gap(pts, duration) => self.push_gap(element, pts, duration),
+                ReceiverItem::Timeout => return Err(gst::FlowError::Eos),
+                ReceiverItem::Flushing => return Err(gst::FlowError::Flushing),
+                ReceiverItem::Error(err) => return Err(err),
+            }
+        }
+    }
+}
 
-                Ok(buffer)
+impl NdiVideoSrc {
+    fn ptz<F: FnOnce(&RecvInstance) -> bool>(
+        &self,
+        element: &gst_base::BaseSrc,
+        command: &str,
+        func: F,
+    ) -> bool {
+        let recv = {
+            let receiver_controller = self.receiver_controller.lock().unwrap();
+            receiver_controller
+                .as_ref()
+                .and_then(ReceiverControlHandle::recv_instance)
+        };
+
+        match recv {
+            Some(recv) => {
+                gst_debug!(self.cat, obj: element, "Sending PTZ command {}", command);
+                func(&recv)
+            }
+            None => {
+                gst_warning!(
+                    self.cat,
+                    obj: element,
+                    "Can't send PTZ command {} without connection",
+                    command
+                );
+                false
             }
-            ReceiverItem::Timeout => Err(gst::FlowError::Eos),
-            ReceiverItem::Flushing => Err(gst::FlowError::Flushing),
-            ReceiverItem::Error(err) => Err(err),
         }
     }
+
+    // Lets downstream know that no data is coming for a while, once caps were configured
+    fn push_gap(&self, element: &gst_base::BaseSrc, pts: gst::ClockTime, duration: gst::ClockTime) {
+        if self.state.lock().unwrap().info.is_none() {
+            return;
+        }
+
+        gst_debug!(
+            self.cat,
+            obj: element,
+            "Pushing gap at {} with duration {}",
+            pts,
+            duration
+        );
+        element
+            .get_static_pad("src")
+            .unwrap()
+            .push_event(gst::Event::new_gap(pts, duration).build());
+    }
 }
 
 pub fn register(plugin: &gst::Plugin) -> Result<(), glib::BoolError> {
@@ -9,12 +9,15 @@ use byte_slice_cast::AsMutSliceOf;
 
 use std::cmp;
 use std::collections::VecDeque;
+use std::ptr;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Condvar, Mutex, Weak};
 use std::thread;
 
 use super::*;
 
+use crate::meta::NdiFrameMetadataMeta;
+
 enum ReceiverInfo {
     Connecting {
         id: usize,
@@ -23,6 +26,9 @@ enum ReceiverInfo {
         video: Option<Weak<ReceiverInner<VideoReceiver>>>,
         audio: Option<Weak<ReceiverInner<AudioReceiver>>>,
         observations: Observations,
+        tally: ReceiverTally,
+        // Requested by the video receiver, if any
+        color_format: NDIlib_recv_color_format_e,
     },
     Connected {
         id: usize,
@@ -32,9 +38,25 @@ enum ReceiverInfo {
         video: Option<Weak<ReceiverInner<VideoReceiver>>>,
         audio: Option<Weak<ReceiverInner<AudioReceiver>>>,
         observations: Observations,
+        tally: ReceiverTally,
+        color_format: NDIlib_recv_color_format_e,
     },
 }
 
+// Tally requested by the video and audio element sharing a receiver. The sender is told about
+// the combination of both.
+#[derive(Debug, Default, Clone, Copy)]
+struct ReceiverTally {
+    video: (bool, bool),
+    audio: (bool, bool),
+}
+
+impl ReceiverTally {
+    fn combined(&self) -> Tally {
+        Tally::new(self.video.0 || self.audio.0, self.video.1 || self.audio.1)
+    }
+}
+
 lazy_static! {
     static ref HASHMAP_RECEIVERS: Mutex<HashMap<usize, ReceiverInfo>> = {
         let m = HashMap::new();
@@ -73,6 +95,9 @@ impl<T: ReceiverType> Clone for Receiver<T> {
 #[derive(Debug)]
 pub enum ReceiverItem<T: ReceiverType> {
     Buffer(gst::Buffer, T::InfoType),
+    // No frames were received for the timeout but the source waits for more, with the running
+    // time and duration of the gap
+    Gap(gst::ClockTime, gst::ClockTime),
     Flushing,
     Timeout,
     Error(gst::FlowError),
@@ -92,6 +117,18 @@ pub struct ReceiverInner<T: ReceiverType> {
     element: glib::WeakRef<gst_base::BaseSrc>,
     timestamp_mode: TimestampMode,
     timeout: u32,
+    timeout_action: TimeoutAction,
+    connect_settings: ConnectSettings,
+
+    // Only used by audio receivers: the output format negotiated with downstream and the
+    // reference level used when converting to 16 bit integer samples
+    audio_format: Arc<Mutex<(gst_audio::AudioFormat, gst_audio::AudioLayout)>>,
+    reference_level: i32,
+
+    // Only used by video receivers: if downstream supports video meta and NDI frames with a
+    // different memory layout than GStreamer's default can be passed on without copying.
+    // Only valid for the video info it was queried for, and reset whenever that changes
+    video_meta_supported: Mutex<(Option<gst_video::VideoInfo>, bool)>,
 
     thread: Mutex<Option<std::thread::JoinHandle<()>>>,
 }
@@ -123,14 +160,32 @@ struct ReceiverQueueInner<T: ReceiverType> {
 
     error: Option<gst::FlowError>,
     timeout: bool,
+
+    // Set while no frames are received and the source should fill the gap downstream
+    gap: Option<(gst::ClockTime, gst::ClockTime)>,
+}
+
+// Everything needed for searching the source again when reconnecting
+#[derive(Debug, Clone)]
+struct ConnectSettings {
+    receiver_ndi_name: String,
+    groups: Option<String>,
+    extra_ips: Option<String>,
+    show_local_sources: bool,
+    connect_timeout: u32,
+    bandwidth: NDIlib_recv_bandwidth_e,
 }
 
+// Delay before retrying after a failed reconnection attempt, doubled after every further failure
+pub(crate) const RECONNECT_DELAY_MIN: time::Duration = time::Duration::from_secs(1);
+pub(crate) const RECONNECT_DELAY_MAX: time::Duration = time::Duration::from_secs(30);
+
 // 100 frames observations window over which we calculate the timestamp drift
 // between sender and receiver. A bigger window allows more smoothing out of
 // network effects
 const WINDOW_LENGTH: usize = 100;
 #[derive(Clone)]
-struct Observations(Arc<Mutex<ObservationsInner>>);
+pub(crate) struct Observations(Arc<Mutex<ObservationsInner>>);
 struct ObservationsInner {
     // NDI timestamp - GStreamer clock time tuples
     values: Vec<(u64, u64)>,
@@ -158,9 +213,9 @@ struct TimeMapping {
     den: u64,
 }
 
-impl Observations {
-    fn new() -> Self {
-        Self(Arc::new(Mutex::new(ObservationsInner {
+impl Default for ObservationsInner {
+    fn default() -> Self {
+        Self {
             values: Vec::with_capacity(WINDOW_LENGTH),
             values_tmp: [(0, 0); WINDOW_LENGTH],
             current_mapping: TimeMapping::default(),
@@ -169,13 +224,24 @@ impl Observations {
             skip_count: 0,
             skip_period: 1,
             skip_period_update_in: WINDOW_LENGTH,
-        })))
+        }
+    }
+}
+
+impl Observations {
+    pub(crate) fn new() -> Self {
+        Self(Arc::new(Mutex::new(ObservationsInner::default())))
+    }
+
+    // Forgets the previous mapping, e.g. after reconnecting to a sender with a different clock
+    pub(crate) fn reset(&self) {
+        *self.0.lock().unwrap() = ObservationsInner::default();
     }
 
     fn process(
         &self,
         cat: gst::DebugCategory,
-        element: &gst_base::BaseSrc,
+        element: &gst::Element,
         time: (gst::ClockTime, gst::ClockTime),
         duration: gst::ClockTime,
     ) -> (gst::ClockTime, gst::ClockTime) {
@@ -352,13 +418,17 @@ impl Default for TimeMapping {
 }
 
 pub struct ReceiverControlHandle<T: ReceiverType> {
+    id: usize,
     queue: ReceiverQueue<T>,
+    audio_format: Arc<Mutex<(gst_audio::AudioFormat, gst_audio::AudioLayout)>>,
 }
 
 impl<T: ReceiverType> Clone for ReceiverControlHandle<T> {
     fn clone(&self) -> Self {
         ReceiverControlHandle {
+            id: self.id,
             queue: self.queue.clone(),
+            audio_format: self.audio_format.clone(),
         }
     }
 }
@@ -380,6 +450,28 @@ impl<T: ReceiverType> ReceiverControlHandle<T> {
         queue.capturing = false;
         (self.queue.0).1.notify_all();
     }
+
+    pub fn set_tally(&self, on_program: bool, on_preview: bool) {
+        set_receiver_tally::<T>(self.id, Some((on_program, on_preview)));
+    }
+
+    /// Only used by audio receivers: the sample format and layout to convert received frames to
+    pub fn set_output_format(
+        &self,
+        format: gst_audio::AudioFormat,
+        layout: gst_audio::AudioLayout,
+    ) {
+        *self.audio_format.lock().unwrap() = (format, layout);
+    }
+
+    /// The NDI receiver once connected, e.g. for controlling PTZ cameras. Unlike the receiver
+    /// itself this is also available while the source waits for frames.
+    pub fn recv_instance(&self) -> Option<RecvInstance> {
+        match HASHMAP_RECEIVERS.lock().unwrap().get(&self.id) {
+            Some(ReceiverInfo::Connected { ref recv, .. }) => Some(recv.clone()),
+            _ => None,
+        }
+    }
 }
 
 impl<T: ReceiverType> Receiver<T> {
@@ -387,6 +479,9 @@ impl<T: ReceiverType> Receiver<T> {
         info: &mut ReceiverInfo,
         timestamp_mode: TimestampMode,
         timeout: u32,
+        timeout_action: TimeoutAction,
+        connect_settings: ConnectSettings,
+        reference_level: i32,
         element: &gst_base::BaseSrc,
         cat: gst::DebugCategory,
     ) -> Self
@@ -421,6 +516,7 @@ impl<T: ReceiverType> Receiver<T> {
                     buffer_queue: VecDeque::with_capacity(5),
                     error: None,
                     timeout: false,
+                    gap: None,
                 }),
                 Condvar::new(),
             ))),
@@ -431,6 +527,14 @@ impl<T: ReceiverType> Receiver<T> {
             element: element.downgrade(),
             timestamp_mode,
             timeout,
+            timeout_action,
+            connect_settings,
+            audio_format: Arc::new(Mutex::new((
+                gst_audio::AUDIO_FORMAT_F32,
+                gst_audio::AudioLayout::Interleaved,
+            ))),
+            reference_level,
+            video_meta_supported: Mutex::new((None, false)),
             thread: Mutex::new(None),
         }));
 
@@ -470,7 +574,9 @@ impl<T: ReceiverType> Receiver<T> {
 
     pub fn receiver_control_handle(&self) -> ReceiverControlHandle<T> {
         ReceiverControlHandle {
+            id: self.0.id,
             queue: self.0.queue.clone(),
+            audio_format: self.0.audio_format.clone(),
         }
     }
 
@@ -502,6 +608,8 @@ impl<T: ReceiverType> Receiver<T> {
                 return ReceiverItem::Flushing;
             } else if let Some((buffer, info)) = queue.buffer_queue.pop_front() {
                 return ReceiverItem::Buffer(buffer, info);
+            } else if let Some((pts, duration)) = queue.gap.take() {
+                return ReceiverItem::Gap(pts, duration);
             }
 
             queue = (self.0.queue.0).1.wait(queue).unwrap();
@@ -543,6 +651,10 @@ impl<T: ReceiverType> Drop for ReceiverInner<T> {
                 } else {
                     *audio = None;
                 }
+                drop(receivers);
+
+                // The remaining element alone decides about the tally now
+                set_receiver_tally::<T>(self.id, None);
                 return;
             }
         }
@@ -560,10 +672,16 @@ pub fn connect_ndi<T: ReceiverType>(
     ip_address: Option<&str>,
     ndi_name: Option<&str>,
     receiver_ndi_name: &str,
+    groups: Option<&str>,
+    extra_ips: Option<&str>,
+    show_local_sources: bool,
     connect_timeout: u32,
     bandwidth: NDIlib_recv_bandwidth_e,
+    color_format: NDIlib_recv_color_format_e,
     timestamp_mode: TimestampMode,
     timeout: u32,
+    timeout_action: TimeoutAction,
+    reference_level: i32,
 ) -> Option<Receiver<T>>
 where
     Receiver<T>: ReceiverCapture<T>,
@@ -571,6 +689,14 @@ where
     gst_debug!(cat, obj: element, "Starting NDI connection...");
 
     let ip_address = ip_address.map(str::to_lowercase);
+    let connect_settings = ConnectSettings {
+        receiver_ndi_name: String::from(receiver_ndi_name),
+        groups: groups.map(String::from),
+        extra_ips: extra_ips.map(String::from),
+        show_local_sources,
+        connect_timeout,
+        bandwidth,
+    };
 
     let mut receivers = HASHMAP_RECEIVERS.lock().unwrap();
 
@@ -615,7 +741,28 @@ where
 
                 return None;
             } else {
-                return Some(Receiver::new(val, timestamp_mode, timeout, element, cat));
+                // Only video receivers care about the color format, and it can only be changed
+                // until connected
+                if T::IS_VIDEO {
+                    if let ReceiverInfo::Connecting {
+                        color_format: ref mut val_color_format,
+                        ..
+                    } = val
+                    {
+                        *val_color_format = color_format;
+                    }
+                }
+
+                return Some(Receiver::new(
+                    val,
+                    timestamp_mode,
+                    timeout,
+                    timeout_action,
+                    connect_settings,
+                    reference_level,
+                    element,
+                    cat,
+                ));
             }
         }
     }
@@ -629,36 +776,82 @@ where
         video: None,
         audio: None,
         observations: Observations::new(),
+        tally: ReceiverTally::default(),
+        color_format: if T::IS_VIDEO {
+            color_format
+        } else {
+            NDIlib_recv_color_format_e::NDIlib_recv_color_format_UYVY_BGRA
+        },
     };
 
-    let receiver = Receiver::new(&mut info, timestamp_mode, timeout, element, cat);
+    let receiver = Receiver::new(
+        &mut info,
+        timestamp_mode,
+        timeout,
+        timeout_action,
+        connect_settings.clone(),
+        reference_level,
+        element,
+        cat,
+    );
 
     receivers.insert(id_receiver, info);
 
-    let receiver_ndi_name = String::from(receiver_ndi_name);
-    let element = element.clone();
+    spawn_connect_thread(cat, element.clone(), id_receiver, connect_settings, false);
+
+    Some(receiver)
+}
+
+// Searches and connects to the source of the receiver in a new thread. When reconnecting, failed
+// attempts are retried with an increasing delay instead of erroring out
+fn spawn_connect_thread(
+    cat: gst::DebugCategory,
+    element: gst_base::BaseSrc,
+    id_receiver: usize,
+    connect_settings: ConnectSettings,
+    reconnect: bool,
+) {
     thread::spawn(move || {
         use std::panic;
 
-        let res = match panic::catch_unwind(move || {
-            connect_ndi_async(
-                cat,
-                &element,
-                id_receiver,
-                receiver_ndi_name,
-                connect_timeout,
-                bandwidth,
-            )
-        }) {
-            Ok(res) => res,
-            Err(_) => Err(Some(gst_error_msg!(
-                gst::LibraryError::Failed,
-                ["Panic while connecting to NDI source"]
-            ))),
+        let mut delay = RECONNECT_DELAY_MIN;
+        let res = loop {
+            let res = match panic::catch_unwind(panic::AssertUnwindSafe(|| {
+                connect_ndi_async(cat, &element, id_receiver, &connect_settings)
+            })) {
+                Ok(res) => res,
+                Err(_) => Err(Some(gst_error_msg!(
+                    gst::LibraryError::Failed,
+                    ["Panic while connecting to NDI source"]
+                ))),
+            };
+
+            match res {
+                Err(Some(ref err)) if reconnect => {
+                    gst_warning!(
+                        cat,
+                        obj: &element,
+                        "Failed to reconnect: {:?}, retrying in {:?}",
+                        err,
+                        delay
+                    );
+
+                    if !wait_for_reconnect(id_receiver, delay) {
+                        break Err(None);
+                    }
+                    delay = cmp::min(delay * 2, RECONNECT_DELAY_MAX);
+                }
+                res => break res,
+            }
         };
 
         match res {
-            Ok(_) => (),
+            Ok(_) => {
+                if reconnect {
+                    gst_debug!(cat, obj: &element, "Reconnected");
+                    post_connection_message(id_receiver, "ndi-reconnected");
+                }
+            }
             Err(None) => {
                 gst_debug!(cat, "Shutting down while connecting");
             }
@@ -707,19 +900,176 @@ where
             }
         }
     });
+}
 
-    Some(receiver)
+// Waits before the next reconnection attempt, returns false if all elements using the receiver
+// were shut down in the meantime
+fn wait_for_reconnect(id_receiver: usize, delay: time::Duration) -> bool {
+    let timer = time::Instant::now();
+    while timer.elapsed() < delay {
+        if !HASHMAP_RECEIVERS.lock().unwrap().contains_key(&id_receiver) {
+            return false;
+        }
+        thread::sleep(time::Duration::from_millis(100));
+    }
+
+    HASHMAP_RECEIVERS.lock().unwrap().contains_key(&id_receiver)
+}
+
+// Tears down the connection after a timeout and searches for the source again, unless the other
+// element sharing the receiver already did so
+fn reconnect_ndi<T: ReceiverType>(
+    receiver: &Receiver<T>,
+    element: &gst_base::BaseSrc,
+    recv: &RecvInstance,
+) {
+    let mut receivers = HASHMAP_RECEIVERS.lock().unwrap();
+    let info = match receivers.get_mut(&receiver.0.id) {
+        None => return,
+        Some(val) => val,
+    };
+
+    let elements = receiver_elements(info);
+
+    let (new_info, ndi_name, ip_address) = match *info {
+        ReceiverInfo::Connected {
+            id,
+            ref ndi_name,
+            ref ip_address,
+            recv: ref val_recv,
+            ref video,
+            ref audio,
+            ref observations,
+            tally,
+            color_format,
+        } if val_recv.ptr_eq(recv) => {
+            gst_debug!(
+                receiver.0.cat,
+                obj: element,
+                "Disconnecting from NDI source with ndi-name '{}' and ip-address '{}'",
+                ndi_name,
+                ip_address,
+            );
+
+            observations.reset();
+
+            if let Some(audio) = audio.as_ref().and_then(|v| v.upgrade()).map(Receiver) {
+                *audio.0.recv.lock().unwrap() = None;
+            }
+            if let Some(video) = video.as_ref().and_then(|v| v.upgrade()).map(Receiver) {
+                *video.0.recv.lock().unwrap() = None;
+            }
+
+            let new_info = ReceiverInfo::Connecting {
+                id,
+                ndi_name: Some(ndi_name.clone()),
+                ip_address: Some(ip_address.clone()),
+                video: video.clone(),
+                audio: audio.clone(),
+                observations: observations.clone(),
+                tally,
+                color_format,
+            };
+
+            (new_info, ndi_name.clone(), ip_address.clone())
+        }
+        // Already reconnecting, or reconnected, because of the other element
+        _ => return,
+    };
+    *info = new_info;
+    drop(receivers);
+
+    post_message(&elements, "ndi-disconnected", &ndi_name, &ip_address);
+
+    spawn_connect_thread(
+        receiver.0.cat,
+        element.clone(),
+        receiver.0.id,
+        receiver.0.connect_settings.clone(),
+        true,
+    );
+}
+
+fn receiver_elements(info: &ReceiverInfo) -> Vec<gst_base::BaseSrc> {
+    let (video, audio) = match info {
+        ReceiverInfo::Connecting {
+            ref video,
+            ref audio,
+            ..
+        } => (video, audio),
+        ReceiverInfo::Connected {
+            ref video,
+            ref audio,
+            ..
+        } => (video, audio),
+    };
+
+    video
+        .as_ref()
+        .and_then(|v| v.upgrade())
+        .and_then(|v| v.element.upgrade())
+        .into_iter()
+        .chain(
+            audio
+                .as_ref()
+                .and_then(|v| v.upgrade())
+                .and_then(|v| v.element.upgrade()),
+        )
+        .collect()
+}
+
+// Posts an element message about the connection state with the NDI name and IP address of the
+// source on all elements using the receiver
+fn post_connection_message(id_receiver: usize, name: &str) {
+    let (elements, ndi_name, ip_address) = {
+        let receivers = HASHMAP_RECEIVERS.lock().unwrap();
+        let info = match receivers.get(&id_receiver) {
+            None => return,
+            Some(val) => val,
+        };
+
+        match info {
+            ReceiverInfo::Connected {
+                ref ndi_name,
+                ref ip_address,
+                ..
+            } => (
+                receiver_elements(info),
+                ndi_name.clone(),
+                ip_address.clone(),
+            ),
+            ReceiverInfo::Connecting { .. } => return,
+        }
+    };
+
+    post_message(&elements, name, &ndi_name, &ip_address);
+}
+
+fn post_message(elements: &[gst_base::BaseSrc], name: &str, ndi_name: &str, ip_address: &str) {
+    for element in elements {
+        let s = gst::Structure::new(
+            name,
+            &[("ndi-name", &ndi_name), ("ip-address", &ip_address)],
+        );
+        let _ = element.post_message(&gst::Message::new_element(s).src(Some(element)).build());
+    }
 }
 
 fn connect_ndi_async(
     cat: gst::DebugCategory,
     element: &gst_base::BaseSrc,
     id_receiver: usize,
-    receiver_ndi_name: String,
-    connect_timeout: u32,
-    bandwidth: NDIlib_recv_bandwidth_e,
+    settings: &ConnectSettings,
 ) -> Result<(), Option<gst::ErrorMessage>> {
-    let mut find = match FindInstance::builder().build() {
+    let mut find = FindInstance::builder().show_local_sources(settings.show_local_sources);
+    if let Some(ref groups) = settings.groups {
+        find = find.groups(groups);
+    }
+    if let Some(ref extra_ips) = settings.extra_ips {
+        find = find.extra_ips(extra_ips);
+    }
+
+    let mut find = match find.build() {
         None => {
             return Err(Some(gst_error_msg!(
                 gst::CoreError::Negotiation,
@@ -730,7 +1080,7 @@ fn connect_ndi_async(
     };
 
     let timer = time::Instant::now();
-    let source = loop {
+    let (source, color_format) = loop {
         let new_sources = find.wait_for_sources(100);
         let sources = find.get_current_sources();
 
@@ -758,16 +1108,17 @@ fn connect_ndi_async(
                 Some(val) => val,
             };
 
-            let (ndi_name, ip_address) = match info {
+            let (ndi_name, ip_address, color_format) = match info {
                 ReceiverInfo::Connecting {
                     ref ndi_name,
                     ref ip_address,
                     ref audio,
                     ref video,
+                    color_format,
                     ..
                 } => {
                     assert!(audio.is_some() || video.is_some());
-                    (ndi_name, ip_address)
+                    (ndi_name, ip_address, *color_format)
                 }
                 ReceiverInfo::Connected { .. } => unreachable!(),
             };
@@ -778,11 +1129,11 @@ fn connect_ndi_async(
             });
 
             if let Some(source) = source {
-                break source.to_owned();
+                break (source.to_owned(), color_format);
             }
         }
 
-        if timer.elapsed().as_millis() >= connect_timeout as u128 {
+        if timer.elapsed().as_millis() >= settings.connect_timeout as u128 {
             return Err(Some(gst_error_msg!(
                 gst::ResourceError::NotFound,
                 ["Stream not found"]
@@ -798,12 +1149,12 @@ fn connect_ndi_async(
         source.ip_address(),
     );
 
-    // FIXME: Ideally we would use NDIlib_recv_color_format_fastest here but that seems to be
-    // broken with interlaced content currently
-    let recv = RecvInstance::builder(&source, &receiver_ndi_name)
-        .bandwidth(bandwidth)
-        .color_format(NDIlib_recv_color_format_e::NDIlib_recv_color_format_UYVY_BGRA)
-        .allow_video_fields(true)
+    gst_debug!(cat, obj: element, "Using color format {:?}", color_format);
+
+    let recv = RecvInstance::builder(&source, &settings.receiver_ndi_name)
+        .bandwidth(settings.bandwidth)
+        .color_format(color_format)
+        .allow_video_fields(allow_video_fields(color_format))
         .build();
     let recv = match recv {
         None => {
@@ -815,8 +1166,6 @@ fn connect_ndi_async(
         Some(recv) => recv,
     };
 
-    recv.set_tally(&Tally::default());
-
     let enable_hw_accel = MetadataFrame::new(0, Some("<ndi_hwaccel enabled=\"true\"/>"));
     recv.send_metadata(&enable_hw_accel);
 
@@ -826,16 +1175,19 @@ fn connect_ndi_async(
         Some(val) => val,
     };
 
-    let (audio, video, observations) = match info {
+    let (audio, video, observations, tally) = match info {
         ReceiverInfo::Connecting {
             ref audio,
             ref video,
             ref observations,
+            tally,
             ..
-        } => (audio.clone(), video.clone(), observations),
+        } => (audio.clone(), video.clone(), observations, *tally),
         ReceiverInfo::Connected { .. } => unreachable!(),
     };
 
+    recv.set_tally(&tally.combined());
+
     assert!(audio.is_some() || video.is_some());
 
     *info = ReceiverInfo::Connected {
@@ -846,6 +1198,8 @@ fn connect_ndi_async(
         video: video.clone(),
         audio: audio.clone(),
         observations: observations.clone(),
+        tally,
+        color_format,
     };
 
     gst_debug!(cat, obj: element, "Started NDI connection");
@@ -867,24 +1221,166 @@ fn connect_ndi_async(
     Ok(())
 }
 
+/// NDI color format to receive with for `color_format`. In auto mode the formats accepted by
+/// downstream of `pad` decide, preferring the ones that can be output without conversion.
+pub(crate) fn get_recv_color_format(
+    cat: gst::DebugCategory,
+    pad: &gst::Pad,
+    color_format: RecvColorFormat,
+) -> NDIlib_recv_color_format_e {
+    use crate::ndisys::NDIlib_recv_color_format_e::*;
+
+    match color_format {
+        RecvColorFormat::UyvyBgra => return NDIlib_recv_color_format_UYVY_BGRA,
+        RecvColorFormat::BgrxBgra => return NDIlib_recv_color_format_BGRX_BGRA,
+        RecvColorFormat::RgbxRgba => return NDIlib_recv_color_format_RGBX_RGBA,
+        RecvColorFormat::UyvyRgba => return NDIlib_recv_color_format_UYVY_RGBA,
+        RecvColorFormat::Fastest => return NDIlib_recv_color_format_fastest,
+        RecvColorFormat::Auto => (),
+    }
+
+    let allowed_caps = match pad.get_allowed_caps() {
+        Some(caps) => caps,
+        None => return NDIlib_recv_color_format_UYVY_BGRA,
+    };
+    gst_debug!(cat, obj: pad, "Selecting color format for caps {}", allowed_caps);
+
+    let accepts = |format: gst_video::VideoFormat| {
+        let caps = gst::Caps::new_simple("video/x-raw", &[("format", &format.to_string())]);
+        allowed_caps.can_intersect(&caps)
+    };
+
+    // The first format of each pair is used for senders without alpha, the second one for
+    // senders with alpha
+    if accepts(gst_video::VideoFormat::Uyvy) {
+        if !accepts(gst_video::VideoFormat::Bgra) && accepts(gst_video::VideoFormat::Rgba) {
+            NDIlib_recv_color_format_UYVY_RGBA
+        } else {
+            NDIlib_recv_color_format_UYVY_BGRA
+        }
+    } else if accepts(gst_video::VideoFormat::Bgrx) || accepts(gst_video::VideoFormat::Bgra) {
+        NDIlib_recv_color_format_BGRX_BGRA
+    } else if accepts(gst_video::VideoFormat::Rgbx) || accepts(gst_video::VideoFormat::Rgba) {
+        NDIlib_recv_color_format_RGBX_RGBA
+    } else {
+        NDIlib_recv_color_format_UYVY_BGRA
+    }
+}
+
+/// Whether to let the SDK output separate fields for interlaced content. Without the
+/// `interlaced-fields` feature they can't be output, and with the fastest color format
+/// fields are not handled correctly by the SDK, so full interleaved frames are requested
+/// in both cases.
+pub(crate) fn allow_video_fields(color_format: NDIlib_recv_color_format_e) -> bool {
+    cfg!(feature = "interlaced-fields")
+        && color_format != NDIlib_recv_color_format_e::NDIlib_recv_color_format_fastest
+}
+
+// Updates the tally of the video or audio element of a receiver, or resets it if `None`, and
+// reports the combined tally to the sender if already connected
+fn set_receiver_tally<T: ReceiverType>(id: usize, tally: Option<(bool, bool)>) {
+    let mut receivers = HASHMAP_RECEIVERS.lock().unwrap();
+    let info = match receivers.get_mut(&id) {
+        None => return,
+        Some(val) => val,
+    };
+
+    let (receiver_tally, recv) = match info {
+        ReceiverInfo::Connecting { ref mut tally, .. } => (tally, None),
+        ReceiverInfo::Connected {
+            ref mut tally,
+            ref recv,
+            ..
+        } => (tally, Some(recv)),
+    };
+
+    let tally = tally.unwrap_or((false, false));
+    if T::IS_VIDEO {
+        receiver_tally.video = tally;
+    } else {
+        receiver_tally.audio = tally;
+    }
+
+    if let Some(recv) = recv {
+        recv.set_tally(&receiver_tally.combined());
+    }
+}
+
 fn receive_thread<T: ReceiverType>(receiver: &Weak<ReceiverInner<T>>)
 where
     Receiver<T>: ReceiverCapture<T>,
 {
-    // First loop until we actually are connected, or an error happened
-    let recv = {
-        let receiver = match receiver.upgrade().map(Receiver) {
-            None => return,
-            Some(receiver) => receiver,
-        };
+    // Whether the connection was lost and we're waiting until reconnected
+    let mut reconnecting = false;
 
-        let element = match receiver.0.element.upgrade() {
-            None => return,
-            Some(element) => element,
+    loop {
+        // First loop until we actually are connected, or an error happened
+        let recv = {
+            let receiver = match receiver.upgrade().map(Receiver) {
+                None => return,
+                Some(receiver) => receiver,
+            };
+
+            let element = match receiver.0.element.upgrade() {
+                None => return,
+                Some(element) => element,
+            };
+
+            let mut recv = receiver.0.recv.lock().unwrap();
+            loop {
+                {
+                    let queue = (receiver.0.queue.0).0.lock().unwrap();
+                    if !queue.capturing {
+                        gst_debug!(receiver.0.cat, obj: &element, "Shutting down");
+                        return;
+                    }
+
+                    // If an error happened in the meantime, just go out of here
+                    if queue.error.is_some() {
+                        gst_error!(
+                            receiver.0.cat,
+                            obj: &element,
+                            "Error while waiting for connection"
+                        );
+                        return;
+                    }
+                }
+
+                if let Some(ref recv) = *recv {
+                    break recv.clone();
+                }
+
+                if reconnecting {
+                    // Keep downstream informed about the missing frames until reconnected
+                    let (new_recv, res) = receiver
+                        .0
+                        .recv_cond
+                        .wait_timeout(recv, time::Duration::from_millis(receiver.0.timeout as u64))
+                        .unwrap();
+                    recv = new_recv;
+                    if res.timed_out() {
+                        queue_gap(&receiver, &element);
+                    }
+                } else {
+                    recv = receiver.0.recv_cond.wait(recv).unwrap();
+                }
+            }
         };
+        reconnecting = false;
 
-        let mut recv = receiver.0.recv.lock().unwrap();
+        // Now first capture frames until the queues are empty so that we're sure that we output
+        // only the very latest frame that is available now
         loop {
+            let receiver = match receiver.upgrade().map(Receiver) {
+                None => return,
+                Some(receiver) => receiver,
+            };
+
+            let element = match receiver.0.element.upgrade() {
+                None => return,
+                Some(element) => element,
+            };
+
             {
                 let queue = (receiver.0.queue.0).0.lock().unwrap();
                 if !queue.capturing {
@@ -903,122 +1399,135 @@ where
                 }
             }
 
-            if let Some(ref recv) = *recv {
-                break recv.clone();
+            let queue = recv.get_queue();
+            if (!T::IS_VIDEO && queue.audio_frames() <= 1)
+                || (T::IS_VIDEO && queue.video_frames() <= 1)
+            {
+                break;
             }
 
-            recv = receiver.0.recv_cond.wait(recv).unwrap();
+            let _ = recv.capture(T::IS_VIDEO, !T::IS_VIDEO, false, 0);
         }
-    };
 
-    // Now first capture frames until the queues are empty so that we're sure that we output only
-    // the very latest frame that is available now
-    loop {
-        let receiver = match receiver.upgrade().map(Receiver) {
-            None => return,
-            Some(receiver) => receiver,
-        };
+        // And if that went fine, capture until we're done or have to reconnect
+        loop {
+            let receiver = match receiver.upgrade().map(Receiver) {
+                None => return,
+                Some(receiver) => receiver,
+            };
 
-        let element = match receiver.0.element.upgrade() {
-            None => return,
-            Some(element) => element,
-        };
+            let element = match receiver.0.element.upgrade() {
+                None => return,
+                Some(element) => element,
+            };
 
-        {
-            let queue = (receiver.0.queue.0).0.lock().unwrap();
-            if !queue.capturing {
-                gst_debug!(receiver.0.cat, obj: &element, "Shutting down");
-                return;
+            {
+                let queue = (receiver.0.queue.0).0.lock().unwrap();
+                if !queue.capturing {
+                    gst_debug!(receiver.0.cat, obj: &element, "Shutting down");
+                    return;
+                }
             }
 
-            // If an error happened in the meantime, just go out of here
-            if queue.error.is_some() {
-                gst_error!(
-                    receiver.0.cat,
-                    obj: &element,
-                    "Error while waiting for connection"
-                );
-                return;
+            // The other element sharing the receiver might have started reconnecting already
+            match *receiver.0.recv.lock().unwrap() {
+                Some(ref current) if current.ptr_eq(&recv) => (),
+                _ => break,
             }
-        }
 
-        let queue = recv.get_queue();
-        if (!T::IS_VIDEO && queue.audio_frames() <= 1) || (T::IS_VIDEO && queue.video_frames() <= 1)
-        {
-            break;
+            let res = receiver.capture_internal(&element, &recv);
+
+            match res {
+                Ok(item) => {
+                    let mut queue = (receiver.0.queue.0).0.lock().unwrap();
+                    while queue.buffer_queue.len() > 5 {
+                        gst_warning!(
+                            receiver.0.cat,
+                            obj: &element,
+                            "Dropping old buffer -- queue has {} items",
+                            queue.buffer_queue.len()
+                        );
+                        queue.buffer_queue.pop_front();
+                    }
+                    queue.buffer_queue.push_back(item);
+                    (receiver.0.queue.0).1.notify_one();
+                }
+                Err(gst::FlowError::Eos) => match receiver.0.timeout_action {
+                    TimeoutAction::Eos => {
+                        gst_debug!(receiver.0.cat, obj: &element, "Signalling EOS");
+                        let mut queue = (receiver.0.queue.0).0.lock().unwrap();
+                        queue.timeout = true;
+                        (receiver.0.queue.0).1.notify_one();
+                    }
+                    TimeoutAction::Error => {
+                        gst_element_error!(
+                            element,
+                            gst::ResourceError::Read,
+                            ["No frames received for {}ms", receiver.0.timeout]
+                        );
+                        let mut queue = (receiver.0.queue.0).0.lock().unwrap();
+                        if queue.error.is_none() {
+                            queue.error = Some(gst::FlowError::Error);
+                        }
+                        (receiver.0.queue.0).1.notify_one();
+                        return;
+                    }
+                    TimeoutAction::Wait => {
+                        gst_debug!(receiver.0.cat, obj: &element, "Waiting for more frames");
+                        queue_gap(&receiver, &element);
+                    }
+                    TimeoutAction::Reconnect => {
+                        gst_debug!(receiver.0.cat, obj: &element, "Reconnecting");
+                        queue_gap(&receiver, &element);
+                        reconnect_ndi(&receiver, &element, &recv);
+                        break;
+                    }
+                },
+                Err(gst::FlowError::CustomError) => {
+                    // Flushing, nothing to be done here except for emptying our queue
+                    let mut queue = (receiver.0.queue.0).0.lock().unwrap();
+                    queue.buffer_queue.clear();
+                    (receiver.0.queue.0).1.notify_one();
+                }
+                Err(err) => {
+                    gst_error!(receiver.0.cat, obj: &element, "Signalling error");
+                    let mut queue = (receiver.0.queue.0).0.lock().unwrap();
+                    if queue.error.is_none() {
+                        queue.error = Some(err);
+                    }
+                    (receiver.0.queue.0).1.notify_one();
+                    return;
+                }
+            }
         }
 
-        let _ = recv.capture(T::IS_VIDEO, !T::IS_VIDEO, false, 0);
+        reconnecting = true;
     }
+}
 
-    // And if that went fine, capture until we're done
-    loop {
-        let receiver = match receiver.upgrade().map(Receiver) {
-            None => break,
-            Some(receiver) => receiver,
-        };
+// Lets the source fill the gap downstream while no frames are received
+fn queue_gap<T: ReceiverType>(receiver: &Receiver<T>, element: &gst_base::BaseSrc) {
+    let clock = match element.get_clock() {
+        None => return,
+        Some(clock) => clock,
+    };
 
-        let element = match receiver.0.element.upgrade() {
-            None => return,
-            Some(element) => element,
-        };
+    let running_time = clock.get_time() - element.get_base_time();
+    let duration = gst::ClockTime::from_mseconds(receiver.0.timeout as u64);
 
-        {
-            let queue = (receiver.0.queue.0).0.lock().unwrap();
-            if !queue.capturing {
-                gst_debug!(receiver.0.cat, obj: &element, "Shutting down");
-                break;
-            }
-        }
+    let mut queue = (receiver.0.queue.0).0.lock().unwrap();
+    if queue.playing && !queue.flushing {
+        queue.gap = Some((running_time, duration));
+        (receiver.0.queue.0).1.notify_one();
+    }
+}
 
-        let res = receiver.capture_internal(&element, &recv);
-
-        match res {
-            Ok(item) => {
-                let mut queue = (receiver.0.queue.0).0.lock().unwrap();
-                while queue.buffer_queue.len() > 5 {
-                    gst_warning!(
-                        receiver.0.cat,
-                        obj: &element,
-                        "Dropping old buffer -- queue has {} items",
-                        queue.buffer_queue.len()
-                    );
-                    queue.buffer_queue.pop_front();
-                }
-                queue.buffer_queue.push_back(item);
-                (receiver.0.queue.0).1.notify_one();
-            }
-            Err(gst::FlowError::Eos) => {
-                gst_debug!(receiver.0.cat, obj: &element, "Signalling EOS");
-                let mut queue = (receiver.0.queue.0).0.lock().unwrap();
-                queue.timeout = true;
-                (receiver.0.queue.0).1.notify_one();
-            }
-            Err(gst::FlowError::CustomError) => {
-                // Flushing, nothing to be done here except for emptying our queue
-                let mut queue = (receiver.0.queue.0).0.lock().unwrap();
-                queue.buffer_queue.clear();
-                (receiver.0.queue.0).1.notify_one();
-            }
-            Err(err) => {
-                gst_error!(receiver.0.cat, obj: &element, "Signalling error");
-                let mut queue = (receiver.0.queue.0).0.lock().unwrap();
-                if queue.error.is_none() {
-                    queue.error = Some(err);
-                }
-                (receiver.0.queue.0).1.notify_one();
-                break;
-            }
-        }
-    }
-}
-
-pub trait ReceiverCapture<T: ReceiverType> {
-    fn capture_internal(
-        &self,
-        element: &gst_base::BaseSrc,
-        recv: &RecvInstance,
-    ) -> Result<(gst::Buffer, T::InfoType), gst::FlowError>;
+pub trait ReceiverCapture<T: ReceiverType> {
+    fn capture_internal(
+        &self,
+        element: &gst_base::BaseSrc,
+        recv: &RecvInstance,
+    ) -> Result<(gst::Buffer, T::InfoType), gst::FlowError>;
 
     fn store_internal(
         storage_video: &mut Option<Weak<ReceiverInner<VideoReceiver>>>,
@@ -1046,79 +1555,79 @@ impl ReceiverCapture<VideoReceiver> for Receiver<VideoReceiver> {
     }
 }
 
-impl<T: ReceiverType> Receiver<T> {
-    fn calculate_timestamp(
-        &self,
-        element: &gst_base::BaseSrc,
-        timestamp: i64,
-        timecode: i64,
-        duration: gst::ClockTime,
-    ) -> Option<(gst::ClockTime, gst::ClockTime)> {
-        let clock = match element.get_clock() {
-            None => return None,
-            Some(clock) => clock,
-        };
+pub(crate) fn calculate_timestamp(
+    cat: gst::DebugCategory,
+    element: &gst::Element,
+    observations: &Observations,
+    timestamp_mode: TimestampMode,
+    timestamp: i64,
+    timecode: i64,
+    duration: gst::ClockTime,
+) -> Option<(gst::ClockTime, gst::ClockTime)> {
+    let clock = match element.get_clock() {
+        None => return None,
+        Some(clock) => clock,
+    };
 
-        // For now take the current running time as PTS. At a later time we
-        // will want to work with the timestamp given by the NDI SDK if available
-        let now = clock.get_time();
-        let base_time = element.get_base_time();
-        let receive_time = now - base_time;
+    // For now take the current running time as PTS. At a later time we
+    // will want to work with the timestamp given by the NDI SDK if available
+    let now = clock.get_time();
+    let base_time = element.get_base_time();
+    let receive_time = now - base_time;
+
+    let real_time_now = gst::ClockTime::from(glib::get_real_time() as u64 * 1000);
+    let timestamp = if timestamp == ndisys::NDIlib_recv_timestamp_undefined {
+        gst::CLOCK_TIME_NONE
+    } else {
+        gst::ClockTime::from(timestamp as u64 * 100)
+    };
+    let timecode = gst::ClockTime::from(timecode as u64 * 100);
 
-        let real_time_now = gst::ClockTime::from(glib::get_real_time() as u64 * 1000);
-        let timestamp = if timestamp == ndisys::NDIlib_recv_timestamp_undefined {
-            gst::CLOCK_TIME_NONE
-        } else {
-            gst::ClockTime::from(timestamp as u64 * 100)
-        };
-        let timecode = gst::ClockTime::from(timecode as u64 * 100);
+    gst_log!(
+        cat,
+        obj: element,
+        "Received frame with timecode {}, timestamp {}, duration {}, receive time {}, local time now {}",
+        timecode,
+        timestamp,
+        duration,
+        receive_time,
+        real_time_now,
+    );
 
-        gst_log!(
-            self.0.cat,
-            obj: element,
-            "Received frame with timecode {}, timestamp {}, duration {}, receive time {}, local time now {}",
-            timecode,
-            timestamp,
+    let (pts, duration) = match timestamp_mode {
+        TimestampMode::ReceiveTime => observations.process(
+            cat,
+            element,
+            (timestamp, receive_time),
             duration,
-            receive_time,
-            real_time_now,
-        );
-
-        let (pts, duration) = match self.0.timestamp_mode {
-            TimestampMode::ReceiveTime => self.0.observations.process(
-                self.0.cat,
-                element,
-                (timestamp, receive_time),
-                duration,
-            ),
-            TimestampMode::Timecode => (timecode, duration),
-            TimestampMode::Timestamp if timestamp.is_none() => (receive_time, duration),
-            TimestampMode::Timestamp => {
-                // Timestamps are relative to the UNIX epoch
-                if real_time_now > timestamp {
-                    let diff = real_time_now - timestamp;
-                    if diff > receive_time {
-                        (0.into(), duration)
-                    } else {
-                        (receive_time - diff, duration)
-                    }
+        ),
+        TimestampMode::Timecode => (timecode, duration),
+        TimestampMode::Timestamp if timestamp.is_none() => (receive_time, duration),
+        TimestampMode::Timestamp => {
+            // Timestamps are relative to the UNIX epoch
+            if real_time_now > timestamp {
+                let diff = real_time_now - timestamp;
+                if diff > receive_time {
+                    (0.into(), duration)
                 } else {
-                    let diff = timestamp - real_time_now;
-                    (receive_time + diff, duration)
+                    (receive_time - diff, duration)
                 }
+            } else {
+                let diff = timestamp - real_time_now;
+                (receive_time + diff, duration)
             }
-        };
+        }
+    };
 
-        gst_log!(
-            self.0.cat,
-            obj: element,
-            "Calculated PTS {}, duration {}",
-            pts,
-            duration,
-        );
+    gst_log!(
+        cat,
+        obj: element,
+        "Calculated PTS {}, duration {}",
+        pts,
+        duration,
+    );
 
-        Some((pts, duration))
-    }
+    Some((pts, duration))
 }
 
 impl ReceiverCapture<AudioReceiver> for Receiver<AudioReceiver> {
@@ -1216,15 +1725,43 @@ impl Receiver<VideoReceiver> {
             return Err(gst::FlowError::CustomError);
         }
 
-        let info = self.create_video_info(element, &video_frame)?;
+        let info = create_video_info(element.upcast_ref(), &video_frame)?;
 
-        let buffer = self.create_video_buffer(element, pts, duration, &info, &video_frame)?;
+        // Until downstream was queried for the new caps the frame is copied into the default
+        // layout
+        let video_meta_supported = {
+            let mut video_meta_supported = self.0.video_meta_supported.lock().unwrap();
+            if video_meta_supported.0.as_ref() != Some(&info) {
+                *video_meta_supported = (Some(info.clone()), false);
+            }
+            video_meta_supported.1
+        };
+
+        let buffer = create_video_buffer(
+            element.upcast_ref(),
+            pts,
+            duration,
+            &info,
+            video_frame,
+            video_meta_supported,
+        )?;
 
         gst_log!(self.0.cat, obj: element, "Produced buffer {:?}", buffer);
 
         Ok((buffer, info))
     }
 
+    pub fn set_video_meta_supported(
+        &self,
+        info: &gst_video::VideoInfo,
+        video_meta_supported: bool,
+    ) {
+        let mut current = self.0.video_meta_supported.lock().unwrap();
+        if current.0.as_ref() == Some(info) {
+            current.1 = video_meta_supported;
+        }
+    }
+
     fn calculate_video_timestamp(
         &self,
         element: &gst_base::BaseSrc,
@@ -1237,214 +1774,322 @@ impl Receiver<VideoReceiver> {
             )
             .unwrap_or(gst::CLOCK_TIME_NONE);
 
-        self.calculate_timestamp(
-            element,
+        calculate_timestamp(
+            self.0.cat,
+            element.upcast_ref(),
+            &self.0.observations,
+            self.0.timestamp_mode,
             video_frame.timestamp(),
             video_frame.timecode(),
             duration,
         )
     }
+}
 
-    fn create_video_info(
-        &self,
-        element: &gst_base::BaseSrc,
-        video_frame: &VideoFrame,
-    ) -> Result<gst_video::VideoInfo, gst::FlowError> {
-        // YV12 and I420 are swapped in the NDI SDK compared to GStreamer
-        let format = match video_frame.fourcc() {
-            ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_UYVY => gst_video::VideoFormat::Uyvy,
-            ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_YV12 => gst_video::VideoFormat::I420,
-            ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_NV12 => gst_video::VideoFormat::Nv12,
-            ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_I420 => gst_video::VideoFormat::Yv12,
-            ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_BGRA => gst_video::VideoFormat::Bgra,
-            ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_BGRX => gst_video::VideoFormat::Bgrx,
-            ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_RGBA => gst_video::VideoFormat::Rgba,
-            ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_RGBX => gst_video::VideoFormat::Rgbx,
-            ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_UYVA => gst_video::VideoFormat::Uyvy,
-        };
-
-        let par = gst::Fraction::approximate_f32(video_frame.picture_aspect_ratio())
-            .unwrap_or(gst::Fraction::new(1, 1))
-            * gst::Fraction::new(video_frame.yres(), video_frame.xres());
+pub(crate) fn create_video_info(
+    element: &gst::Element,
+    video_frame: &VideoFrame,
+) -> Result<gst_video::VideoInfo, gst::FlowError> {
+    // YV12 and I420 are swapped in the NDI SDK compared to GStreamer
+    let format = match video_frame.fourcc() {
+        ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_UYVY => gst_video::VideoFormat::Uyvy,
+        ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_YV12 => gst_video::VideoFormat::I420,
+        ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_NV12 => gst_video::VideoFormat::Nv12,
+        ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_I420 => gst_video::VideoFormat::Yv12,
+        ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_BGRA => gst_video::VideoFormat::Bgra,
+        ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_BGRX => gst_video::VideoFormat::Bgrx,
+        ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_RGBA => gst_video::VideoFormat::Rgba,
+        ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_RGBX => gst_video::VideoFormat::Rgbx,
+        // There is no UYVY with alpha in GStreamer, so UYVA is converted to AYUV
+        ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_UYVA => gst_video::VideoFormat::Ayuv,
+    };
 
-        #[cfg(feature = "interlaced-fields")]
-        {
-            let mut builder = gst_video::VideoInfo::new(
-                format,
-                video_frame.xres() as u32,
-                video_frame.yres() as u32,
-            )
-            .fps(gst::Fraction::from(video_frame.frame_rate()))
-            .par(par)
-            .interlace_mode(match video_frame.frame_format_type() {
-                ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_progressive => {
-                    gst_video::VideoInterlaceMode::Progressive
-                }
-                ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved => {
-                    gst_video::VideoInterlaceMode::Interleaved
-                }
-                _ => gst_video::VideoInterlaceMode::Alternate,
-            });
+    let par = gst::Fraction::approximate_f32(video_frame.picture_aspect_ratio())
+        .unwrap_or(gst::Fraction::new(1, 1))
+        * gst::Fraction::new(video_frame.yres(), video_frame.xres());
 
-            if video_frame.frame_format_type()
-                == ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved
-            {
-                builder = builder.field_order(gst_video::VideoFieldOrder::TopFieldFirst);
+    #[cfg(feature = "interlaced-fields")]
+    {
+        let mut builder = gst_video::VideoInfo::new(
+            format,
+            video_frame.xres() as u32,
+            video_frame.yres() as u32,
+        )
+        .fps(gst::Fraction::from(video_frame.frame_rate()))
+        .par(par)
+        .interlace_mode(match video_frame.frame_format_type() {
+            ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_progressive => {
+                gst_video::VideoInterlaceMode::Progressive
             }
+            ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved => {
+                gst_video::VideoInterlaceMode::Interleaved
+            }
+            _ => gst_video::VideoInterlaceMode::Alternate,
+        });
 
-            builder.build().ok_or_else(|| {
-                gst_element_error!(
-                    element,
-                    gst::StreamError::Format,
-                    ["Invalid video format configuration"]
-                );
-
-                gst::FlowError::NotNegotiated
-            })
+        if video_frame.frame_format_type()
+            == ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved
+        {
+            builder = builder.field_order(gst_video::VideoFieldOrder::TopFieldFirst);
         }
 
-        #[cfg(not(feature = "interlaced-fields"))]
-        {
-            if video_frame.frame_format_type()
-                != ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_progressive
-                && video_frame.frame_format_type()
-                    != ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved
-            {
-                gst_element_error!(
-                    element,
-                    gst::StreamError::Format,
-                    ["Separate field interlacing not supported"]
-                );
-                return Err(gst::FlowError::NotNegotiated);
-            }
+        builder.build().ok_or_else(|| {
+            gst_element_error!(
+                element,
+                gst::StreamError::Format,
+                ["Invalid video format configuration"]
+            );
 
-            let mut builder = gst_video::VideoInfo::new(
-                format,
-                video_frame.xres() as u32,
-                video_frame.yres() as u32,
-            )
-            .fps(gst::Fraction::from(video_frame.frame_rate()))
-            .par(par)
-            .interlace_mode(
-                if video_frame.frame_format_type()
-                    == ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_progressive
-                {
-                    gst_video::VideoInterlaceMode::Progressive
-                } else {
-                    gst_video::VideoInterlaceMode::Interleaved
-                },
+            gst::FlowError::NotNegotiated
+        })
+    }
+
+    #[cfg(not(feature = "interlaced-fields"))]
+    {
+        if video_frame.frame_format_type()
+            != ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_progressive
+            && video_frame.frame_format_type()
+                != ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved
+        {
+            gst_element_error!(
+                element,
+                gst::StreamError::Format,
+                ["Separate field interlacing not supported"]
             );
+            return Err(gst::FlowError::NotNegotiated);
+        }
 
+        let mut builder = gst_video::VideoInfo::new(
+            format,
+            video_frame.xres() as u32,
+            video_frame.yres() as u32,
+        )
+        .fps(gst::Fraction::from(video_frame.frame_rate()))
+        .par(par)
+        .interlace_mode(
             if video_frame.frame_format_type()
-                == ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved
+                == ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_progressive
             {
-                builder = builder.field_order(gst_video::VideoFieldOrder::TopFieldFirst);
-            }
-
-            builder.build().ok_or_else(|| {
-                gst_element_error!(
-                    element,
-                    gst::StreamError::Format,
-                    ["Invalid video format configuration"]
-                );
+                gst_video::VideoInterlaceMode::Progressive
+            } else {
+                gst_video::VideoInterlaceMode::Interleaved
+            },
+        );
 
-                gst::FlowError::NotNegotiated
-            })
+        if video_frame.frame_format_type()
+            == ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved
+        {
+            builder = builder.field_order(gst_video::VideoFieldOrder::TopFieldFirst);
         }
+
+        builder.build().ok_or_else(|| {
+            gst_element_error!(
+                element,
+                gst::StreamError::Format,
+                ["Invalid video format configuration"]
+            );
+
+            gst::FlowError::NotNegotiated
+        })
     }
+}
 
-    fn create_video_buffer(
-        &self,
-        element: &gst_base::BaseSrc,
-        pts: gst::ClockTime,
-        duration: gst::ClockTime,
-        info: &gst_video::VideoInfo,
-        video_frame: &VideoFrame,
-    ) -> Result<gst::Buffer, gst::FlowError> {
-        let mut buffer = gst::Buffer::with_size(info.size()).unwrap();
-        {
-            let buffer = buffer.get_mut().unwrap();
-            buffer.set_pts(pts);
-            buffer.set_duration(duration);
+pub(crate) fn create_video_buffer(
+    element: &gst::Element,
+    pts: gst::ClockTime,
+    duration: gst::ClockTime,
+    info: &gst_video::VideoInfo,
+    video_frame: VideoFrame,
+    video_meta_supported: bool,
+) -> Result<gst::Buffer, gst::FlowError> {
+    let mut buffer = gst::Buffer::new();
+    {
+        let buffer = buffer.get_mut().unwrap();
+        buffer.set_pts(pts);
+        buffer.set_duration(duration);
 
-            #[cfg(feature = "reference-timestamps")]
-            {
+        #[cfg(feature = "reference-timestamps")]
+        {
+            gst::ReferenceTimestampMeta::add(
+                buffer,
+                &*TIMECODE_CAPS,
+                gst::ClockTime::from(video_frame.timecode() as u64 * 100),
+                gst::CLOCK_TIME_NONE,
+            );
+            if video_frame.timestamp() != ndisys::NDIlib_recv_timestamp_undefined {
                 gst::ReferenceTimestampMeta::add(
                     buffer,
-                    &*TIMECODE_CAPS,
-                    gst::ClockTime::from(video_frame.timecode() as u64 * 100),
+                    &*TIMESTAMP_CAPS,
+                    gst::ClockTime::from(video_frame.timestamp() as u64 * 100),
                     gst::CLOCK_TIME_NONE,
                 );
-                if video_frame.timestamp() != ndisys::NDIlib_recv_timestamp_undefined {
-                    gst::ReferenceTimestampMeta::add(
-                        buffer,
-                        &*TIMESTAMP_CAPS,
-                        gst::ClockTime::from(video_frame.timestamp() as u64 * 100),
-                        gst::CLOCK_TIME_NONE,
-                    );
-                }
             }
+        }
 
-            #[cfg(feature = "interlaced-fields")]
-            {
-                match video_frame.frame_format_type() {
-                    ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved => {
-                        buffer.set_video_flags(
-                            gst_video::VideoBufferFlags::INTERLACED
-                                | gst_video::VideoBufferFlags::TFF,
-                        );
-                    }
-                    ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_0 => {
-                        buffer.set_video_flags(
-                            gst_video::VideoBufferFlags::INTERLACED
-                                | gst_video::VideoBufferFlags::TOP_FIELD,
-                        );
-                    }
-                    ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_1 => {
-                        buffer.set_video_flags(
-                            gst_video::VideoBufferFlags::INTERLACED
-                                | gst_video::VideoBufferFlags::BOTTOM_FIELD,
-                        );
-                    }
-                    _ => (),
-                };
-            }
+        if let Some(metadata) = video_frame.metadata() {
+            NdiFrameMetadataMeta::add(buffer, metadata);
+        }
 
-            #[cfg(not(feature = "interlaced-fields"))]
-            {
-                if video_frame.frame_format_type()
-                    == ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved
-                {
+        #[cfg(feature = "interlaced-fields")]
+        {
+            match video_frame.frame_format_type() {
+                ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved => {
+                    buffer.set_video_flags(
+                        gst_video::VideoBufferFlags::INTERLACED
+                            | gst_video::VideoBufferFlags::TFF,
+                    );
+                }
+                ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_0 => {
                     buffer.set_video_flags(
-                        gst_video::VideoBufferFlags::INTERLACED | gst_video::VideoBufferFlags::TFF,
+                        gst_video::VideoBufferFlags::INTERLACED
+                            | gst_video::VideoBufferFlags::TOP_FIELD,
                     );
                 }
+                ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_1 => {
+                    buffer.set_video_flags(
+                        gst_video::VideoBufferFlags::INTERLACED
+                            | gst_video::VideoBufferFlags::BOTTOM_FIELD,
+                    );
+                }
+                _ => (),
+            };
+        }
+
+        #[cfg(not(feature = "interlaced-fields"))]
+        {
+            if video_frame.frame_format_type()
+                == ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved
+            {
+                buffer.set_video_flags(
+                    gst_video::VideoBufferFlags::INTERLACED | gst_video::VideoBufferFlags::TFF,
+                );
             }
         }
+    }
 
-        self.copy_video_frame(element, info, buffer, video_frame)
+    match video_frame_layout(info, &video_frame) {
+        Some((offset, stride))
+            if video_meta_supported
+                || (offset == &info.offset()[..offset.len()]
+                    && stride == &info.stride()[..stride.len()]) =>
+        {
+            wrap_video_frame(info, buffer, video_frame, &offset, &stride)
+        }
+        _ => {
+            buffer
+                .get_mut()
+                .unwrap()
+                .append_memory(gst::Memory::with_size(info.size()));
+            copy_video_frame(element, info, buffer, &video_frame)
+        }
     }
+}
 
-    fn copy_video_frame(
-        &self,
-        _element: &gst_base::BaseSrc,
-        info: &gst_video::VideoInfo,
-        buffer: gst::Buffer,
-        video_frame: &VideoFrame,
-    ) -> Result<gst::Buffer, gst::FlowError> {
-        let mut vframe = gst_video::VideoFrame::from_buffer_writable(buffer, info).unwrap();
-
-        match info.format() {
-            gst_video::VideoFormat::Uyvy
-            | gst_video::VideoFormat::Bgra
-            | gst_video::VideoFormat::Bgrx
-            | gst_video::VideoFormat::Rgba
-            | gst_video::VideoFormat::Rgbx => {
-                let line_bytes = if info.format() == gst_video::VideoFormat::Uyvy {
-                    2 * vframe.width() as usize
-                } else {
-                    4 * vframe.width() as usize
-                };
+// Whether downstream of the pad can handle video meta for the given caps, in which case frames
+// with a different memory layout than GStreamer's default can be passed on without copying
+pub(crate) fn video_meta_supported_downstream(pad: &gst::Pad, caps: &gst::Caps) -> bool {
+    let mut query = gst::Query::new_allocation(caps, false);
+    pad.peer_query(&mut query)
+        && query
+            .find_allocation_meta::<gst_video::VideoMeta>()
+            .is_some()
+}
+
+// Plane offsets and strides of the NDI frame if it can be passed downstream as is
+fn video_frame_layout(
+    info: &gst_video::VideoInfo,
+    video_frame: &VideoFrame,
+) -> Option<(Vec<usize>, Vec<i32>)> {
+    // FIXME: The layout of separate fields is not validated yet, see VideoFrame::data()
+    if video_frame.frame_format_type()
+        == ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_0
+        || video_frame.frame_format_type()
+            == ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_1
+    {
+        return None;
+    }
+
+    let stride = video_frame.line_stride_in_bytes();
+    let plane_size = video_frame.yres() as usize * stride as usize;
+
+    match info.format() {
+        gst_video::VideoFormat::Uyvy
+        | gst_video::VideoFormat::Bgra
+        | gst_video::VideoFormat::Bgrx
+        | gst_video::VideoFormat::Rgba
+        | gst_video::VideoFormat::Rgbx => Some((vec![0], vec![stride])),
+        gst_video::VideoFormat::Nv12 => Some((vec![0, plane_size], vec![stride, stride])),
+        gst_video::VideoFormat::Yv12 | gst_video::VideoFormat::I420 => {
+            let chroma_size = (video_frame.yres() as usize + 1) / 2 * (stride / 2) as usize;
+            Some((
+                vec![0, plane_size, plane_size + chroma_size],
+                vec![stride, stride / 2, stride / 2],
+            ))
+        }
+        // UYVA needs to be converted to AYUV
+        _ => None,
+    }
+}
+
+fn wrap_video_frame(
+    info: &gst_video::VideoInfo,
+    mut buffer: gst::Buffer,
+    video_frame: VideoFrame,
+    offset: &[usize],
+    stride: &[i32],
+) -> Result<gst::Buffer, gst::FlowError> {
+    {
+        let buffer = buffer.get_mut().unwrap();
+        buffer.append_memory(gst::Memory::from_slice(video_frame.into_memory()));
+        gst_video::VideoMeta::add_full(
+            buffer,
+            gst_video::VideoFrameFlags::NONE,
+            info.format(),
+            info.width(),
+            info.height(),
+            offset,
+            stride,
+        );
+    }
+
+    Ok(buffer)
+}
+
+fn copy_video_frame(
+    _element: &gst::Element,
+    info: &gst_video::VideoInfo,
+    buffer: gst::Buffer,
+    video_frame: &VideoFrame,
+) -> Result<gst::Buffer, gst::FlowError> {
+    let mut vframe = gst_video::VideoFrame::from_buffer_writable(buffer, info).unwrap();
+
+    match info.format() {
+        gst_video::VideoFormat::Uyvy
+        | gst_video::VideoFormat::Bgra
+        | gst_video::VideoFormat::Bgrx
+        | gst_video::VideoFormat::Rgba
+        | gst_video::VideoFormat::Rgbx => {
+            let line_bytes = if info.format() == gst_video::VideoFormat::Uyvy {
+                2 * vframe.width() as usize
+            } else {
+                4 * vframe.width() as usize
+            };
+            let dest_stride = vframe.plane_stride()[0] as usize;
+            let dest = vframe.plane_data_mut(0).unwrap();
+            let src_stride = video_frame.line_stride_in_bytes() as usize;
+            let src = video_frame.data();
+
+            for (dest, src) in dest
+                .chunks_exact_mut(dest_stride)
+                .zip(src.chunks_exact(src_stride))
+            {
+                dest[..line_bytes].copy_from_slice(&src[..line_bytes]);
+            }
+        }
+        gst_video::VideoFormat::Nv12 => {
+            // First plane
+            {
+                let line_bytes = vframe.width() as usize;
                 let dest_stride = vframe.plane_stride()[0] as usize;
                 let dest = vframe.plane_data_mut(0).unwrap();
                 let src_stride = video_frame.line_stride_in_bytes() as usize;
@@ -1454,100 +2099,104 @@ impl Receiver<VideoReceiver> {
                     .chunks_exact_mut(dest_stride)
                     .zip(src.chunks_exact(src_stride))
                 {
-                    dest.copy_from_slice(src);
-                    dest.copy_from_slice(&src[..line_bytes]);
+                    dest[..line_bytes].copy_from_slice(&src[..line_bytes]);
                 }
             }
-            gst_video::VideoFormat::Nv12 => {
-                // First plane
-                {
-                    let line_bytes = vframe.width() as usize;
-                    let dest_stride = vframe.plane_stride()[0] as usize;
-                    let dest = vframe.plane_data_mut(0).unwrap();
-                    let src_stride = video_frame.line_stride_in_bytes() as usize;
-                    let src = video_frame.data();
-
-                    for (dest, src) in dest
-                        .chunks_exact_mut(dest_stride)
-                        .zip(src.chunks_exact(src_stride))
-                    {
-                        dest.copy_from_slice(&src[..line_bytes]);
-                    }
-                }
 
-                // Second plane
+            // Second plane
+            {
+                let line_bytes = vframe.width() as usize;
+                let dest_stride = vframe.plane_stride()[1] as usize;
+                let dest = vframe.plane_data_mut(1).unwrap();
+                let src_stride = video_frame.line_stride_in_bytes() as usize;
+                let src = &video_frame.data()[(video_frame.yres() as usize * src_stride)..];
+
+                for (dest, src) in dest
+                    .chunks_exact_mut(dest_stride)
+                    .zip(src.chunks_exact(src_stride))
                 {
-                    let line_bytes = vframe.width() as usize;
-                    let dest_stride = vframe.plane_stride()[1] as usize;
-                    let dest = vframe.plane_data_mut(1).unwrap();
-                    let src_stride = video_frame.line_stride_in_bytes() as usize;
-                    let src = &video_frame.data()[(video_frame.yres() as usize * src_stride)..];
-
-                    for (dest, src) in dest
-                        .chunks_exact_mut(dest_stride)
-                        .zip(src.chunks_exact(src_stride))
-                    {
-                        dest.copy_from_slice(&src[..line_bytes]);
-                    }
+                    dest[..line_bytes].copy_from_slice(&src[..line_bytes]);
                 }
             }
-            gst_video::VideoFormat::Yv12 | gst_video::VideoFormat::I420 => {
-                // First plane
+        }
+        gst_video::VideoFormat::Yv12 | gst_video::VideoFormat::I420 => {
+            // First plane
+            {
+                let line_bytes = vframe.width() as usize;
+                let dest_stride = vframe.plane_stride()[0] as usize;
+                let dest = vframe.plane_data_mut(0).unwrap();
+                let src_stride = video_frame.line_stride_in_bytes() as usize;
+                let src = video_frame.data();
+
+                for (dest, src) in dest
+                    .chunks_exact_mut(dest_stride)
+                    .zip(src.chunks_exact(src_stride))
                 {
-                    let line_bytes = vframe.width() as usize;
-                    let dest_stride = vframe.plane_stride()[0] as usize;
-                    let dest = vframe.plane_data_mut(0).unwrap();
-                    let src_stride = video_frame.line_stride_in_bytes() as usize;
-                    let src = video_frame.data();
-
-                    for (dest, src) in dest
-                        .chunks_exact_mut(dest_stride)
-                        .zip(src.chunks_exact(src_stride))
-                    {
-                        dest.copy_from_slice(&src[..line_bytes]);
-                    }
+                    dest[..line_bytes].copy_from_slice(&src[..line_bytes]);
                 }
+            }
+
+            // Second plane
+            {
+                let line_bytes = (vframe.width() as usize + 1) / 2;
+                let dest_stride = vframe.plane_stride()[1] as usize;
+                let dest = vframe.plane_data_mut(1).unwrap();
+                let src_stride = video_frame.line_stride_in_bytes() as usize;
+                let src_stride1 = video_frame.line_stride_in_bytes() as usize / 2;
+                let src = &video_frame.data()[(video_frame.yres() as usize * src_stride)..];
 
-                // Second plane
+                for (dest, src) in dest
+                    .chunks_exact_mut(dest_stride)
+                    .zip(src.chunks_exact(src_stride1))
                 {
-                    let line_bytes = (vframe.width() as usize + 1) / 2;
-                    let dest_stride = vframe.plane_stride()[1] as usize;
-                    let dest = vframe.plane_data_mut(1).unwrap();
-                    let src_stride = video_frame.line_stride_in_bytes() as usize;
-                    let src_stride1 = video_frame.line_stride_in_bytes() as usize / 2;
-                    let src = &video_frame.data()[(video_frame.yres() as usize * src_stride)..];
-
-                    for (dest, src) in dest
-                        .chunks_exact_mut(dest_stride)
-                        .zip(src.chunks_exact(src_stride1))
-                    {
-                        dest.copy_from_slice(&src[..line_bytes]);
-                    }
+                    dest[..line_bytes].copy_from_slice(&src[..line_bytes]);
                 }
+            }
 
-                // Third plane
+            // Third plane
+            {
+                let line_bytes = (vframe.width() as usize + 1) / 2;
+                let dest_stride = vframe.plane_stride()[2] as usize;
+                let dest = vframe.plane_data_mut(2).unwrap();
+                let src_stride = video_frame.line_stride_in_bytes() as usize;
+                let src_stride1 = video_frame.line_stride_in_bytes() as usize / 2;
+                let src = &video_frame.data()[(video_frame.yres() as usize * src_stride
+                    + (video_frame.yres() as usize + 1) / 2 * src_stride1)..];
+
+                for (dest, src) in dest
+                    .chunks_exact_mut(dest_stride)
+                    .zip(src.chunks_exact(src_stride1))
                 {
-                    let line_bytes = (vframe.width() as usize + 1) / 2;
-                    let dest_stride = vframe.plane_stride()[2] as usize;
-                    let dest = vframe.plane_data_mut(2).unwrap();
-                    let src_stride = video_frame.line_stride_in_bytes() as usize;
-                    let src_stride1 = video_frame.line_stride_in_bytes() as usize / 2;
-                    let src = &video_frame.data()[(video_frame.yres() as usize * src_stride
-                        + (video_frame.yres() as usize + 1) / 2 * src_stride1)..];
-
-                    for (dest, src) in dest
-                        .chunks_exact_mut(dest_stride)
-                        .zip(src.chunks_exact(src_stride1))
-                    {
-                        dest.copy_from_slice(&src[..line_bytes]);
-                    }
+                    dest[..line_bytes].copy_from_slice(&src[..line_bytes]);
                 }
             }
-            _ => unreachable!(),
         }
-
-        Ok(vframe.into_buffer())
+        gst_video::VideoFormat::Ayuv => {
+            let width = vframe.width() as usize;
+            let height = vframe.height() as usize;
+            let dest_stride = vframe.plane_stride()[0] as usize;
+            let dest = vframe.plane_data_mut(0).unwrap();
+            let src_stride = video_frame.line_stride_in_bytes() as usize;
+            let (src, alpha) = video_frame.data().split_at(height * src_stride);
+
+            for ((dest, src), alpha) in dest
+                .chunks_exact_mut(dest_stride)
+                .zip(src.chunks_exact(src_stride))
+                .zip(alpha.chunks_exact(width))
+            {
+                for (x, (dest, alpha)) in dest.chunks_exact_mut(4).zip(alpha).enumerate() {
+                    let uyvy = &src[(x / 2) * 4..];
+                    dest[0] = *alpha;
+                    dest[1] = uyvy[1 + 2 * (x % 2)];
+                    dest[2] = uyvy[0];
+                    dest[3] = uyvy[2];
+                }
+            }
+        }
+        _ => unreachable!(),
     }
+
+    Ok(vframe.into_buffer())
 }
 
 impl Receiver<AudioReceiver> {
@@ -1626,9 +2275,17 @@ impl Receiver<AudioReceiver> {
             return Err(gst::FlowError::CustomError);
         }
 
-        let info = self.create_audio_info(element, &audio_frame)?;
+        let (format, layout) = *self.0.audio_format.lock().unwrap();
+        let info = create_audio_info(element.upcast_ref(), &audio_frame, format, layout)?;
 
-        let buffer = self.create_audio_buffer(element, pts, duration, &info, &audio_frame)?;
+        let buffer = create_audio_buffer(
+            element.upcast_ref(),
+            pts,
+            duration,
+            &info,
+            &audio_frame,
+            self.0.reference_level,
+        )?;
 
         gst_log!(self.0.cat, obj: element, "Produced buffer {:?}", buffer);
 
@@ -1647,80 +2304,111 @@ impl Receiver<AudioReceiver> {
             )
             .unwrap_or(gst::CLOCK_TIME_NONE);
 
-        self.calculate_timestamp(
-            element,
+        calculate_timestamp(
+            self.0.cat,
+            element.upcast_ref(),
+            &self.0.observations,
+            self.0.timestamp_mode,
             audio_frame.timestamp(),
             audio_frame.timecode(),
             duration,
         )
     }
+}
 
-    fn create_audio_info(
-        &self,
-        element: &gst_base::BaseSrc,
-        audio_frame: &AudioFrame,
-    ) -> Result<gst_audio::AudioInfo, gst::FlowError> {
-        let builder = gst_audio::AudioInfo::new(
-            gst_audio::AUDIO_FORMAT_S16,
-            audio_frame.sample_rate() as u32,
-            audio_frame.no_channels() as u32,
+pub(crate) fn create_audio_info(
+    element: &gst::Element,
+    audio_frame: &AudioFrame,
+    format: gst_audio::AudioFormat,
+    layout: gst_audio::AudioLayout,
+) -> Result<gst_audio::AudioInfo, gst::FlowError> {
+    let builder = gst_audio::AudioInfo::new(
+        format,
+        audio_frame.sample_rate() as u32,
+        audio_frame.no_channels() as u32,
+    )
+    .layout(layout);
+
+    builder.build().ok_or_else(|| {
+        gst_element_error!(
+            element,
+            gst::StreamError::Format,
+            ["Invalid audio format configuration"]
         );
 
-        builder.build().ok_or_else(|| {
-            gst_element_error!(
-                element,
-                gst::StreamError::Format,
-                ["Invalid audio format configuration"]
-            );
-
-            gst::FlowError::NotNegotiated
-        })
-    }
+        gst::FlowError::NotNegotiated
+    })
+}
 
-    fn create_audio_buffer(
-        &self,
-        _element: &gst_base::BaseSrc,
-        pts: gst::ClockTime,
-        duration: gst::ClockTime,
-        info: &gst_audio::AudioInfo,
-        audio_frame: &AudioFrame,
-    ) -> Result<gst::Buffer, gst::FlowError> {
-        // We multiply by 2 because is the size in bytes of an i16 variable
-        let buff_size = (audio_frame.no_samples() as u32 * info.bpf()) as usize;
-        let mut buffer = gst::Buffer::with_size(buff_size).unwrap();
-        {
-            let buffer = buffer.get_mut().unwrap();
+pub(crate) fn create_audio_buffer(
+    _element: &gst::Element,
+    pts: gst::ClockTime,
+    duration: gst::ClockTime,
+    info: &gst_audio::AudioInfo,
+    audio_frame: &AudioFrame,
+    reference_level: i32,
+) -> Result<gst::Buffer, gst::FlowError> {
+    let buff_size = (audio_frame.no_samples() as u32 * info.bpf()) as usize;
+    let mut buffer = gst::Buffer::with_size(buff_size).unwrap();
+    {
+        let buffer = buffer.get_mut().unwrap();
 
-            buffer.set_pts(pts);
-            buffer.set_duration(duration);
+        buffer.set_pts(pts);
+        buffer.set_duration(duration);
 
-            #[cfg(feature = "reference-timestamps")]
-            {
+        #[cfg(feature = "reference-timestamps")]
+        {
+            gst::ReferenceTimestampMeta::add(
+                buffer,
+                &*TIMECODE_CAPS,
+                gst::ClockTime::from(audio_frame.timecode() as u64 * 100),
+                gst::CLOCK_TIME_NONE,
+            );
+            if audio_frame.timestamp() != ndisys::NDIlib_recv_timestamp_undefined {
                 gst::ReferenceTimestampMeta::add(
                     buffer,
-                    &*TIMECODE_CAPS,
-                    gst::ClockTime::from(audio_frame.timecode() as u64 * 100),
+                    &*TIMESTAMP_CAPS,
+                    gst::ClockTime::from(audio_frame.timestamp() as u64 * 100),
                     gst::CLOCK_TIME_NONE,
                 );
-                if audio_frame.timestamp() != ndisys::NDIlib_recv_timestamp_undefined {
-                    gst::ReferenceTimestampMeta::add(
-                        buffer,
-                        &*TIMESTAMP_CAPS,
-                        gst::ClockTime::from(audio_frame.timestamp() as u64 * 100),
-                        gst::CLOCK_TIME_NONE,
+            }
+        }
+
+        if let Some(metadata) = audio_frame.metadata() {
+            NdiFrameMetadataMeta::add(buffer, metadata);
+        }
+
+        // Non-interleaved audio always needs an audio meta. The planes are packed right after
+        // each other, which is what a meta without offsets describes
+        #[cfg(feature = "audio-meta")]
+        {
+            if info.layout() == gst_audio::AudioLayout::NonInterleaved {
+                use glib::translate::ToGlibPtr;
+
+                unsafe {
+                    gst_audio_sys::gst_buffer_add_audio_meta(
+                        buffer.as_mut_ptr(),
+                        info.to_glib_none().0,
+                        audio_frame.no_samples() as usize,
+                        ptr::null_mut(),
                     );
                 }
             }
+        }
 
+        let mut map = buffer.map_writable().unwrap();
+        if info.format() == gst_audio::AUDIO_FORMAT_S16 {
             audio_frame.copy_to_interleaved_16s(
-                buffer
-                    .map_writable()
-                    .unwrap()
-                    .as_mut_slice_of::<i16>()
-                    .unwrap(),
+                map.as_mut_slice_of::<i16>().unwrap(),
+                reference_level,
             );
+        } else if info.layout() == gst_audio::AudioLayout::NonInterleaved {
+            // NDI audio is planar float already, only the channel stride might differ
+            audio_frame.copy_to_planar_32f(map.as_mut_slice_of::<f32>().unwrap());
+        } else {
+            audio_frame.copy_to_interleaved_32f(map.as_mut_slice_of::<f32>().unwrap());
         }
-
-        Ok(buffer)
     }
+
+    Ok(buffer)
 }
@@ -1,20 +1,102 @@
-use std::ffi::CString;
+use std::error;
+use std::ffi::{CStr, CString};
+use std::fmt;
 use std::ptr::{null, null_mut};
 use std::os::raw::c_char;
 use std::mem::size_of;
+use std::i32;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::{cmp, thread, time};
+
+use gst::prelude::*;
+use gst_video::prelude::*;
 
 use super::*;
+use crate::meta::NdiFrameMetadataMeta;
+
+lazy_static! {
+    // Identifies the sender as GStreamer to receivers and monitoring tools
+    static ref DEFAULT_CONNECTION_METADATA: String = format!(
+        "<ndi_product long_name=\"GStreamer NDI Plugin\" short_name=\"GStreamer\" \
+         manufacturer=\"GStreamer\" version=\"{}-{}\"/>\
+         <ndi_capabilities ntk_ptz=\"false\"/>",
+        env!("CARGO_PKG_VERSION"),
+        env!("COMMIT_ID")
+    );
+}
 
 unsafe impl Send for SendInstance {}
 
 pub struct SendInstance {
-    instance: NDIlib_send_instance_t,
+    instance: Arc<SendInstanceInner>,
+    // Frame passed to the last asynchronous send. The SDK keeps using its data until the next
+    // asynchronous send or a flush, so it has to be kept alive until then.
+    async_frame: Option<NDISendVideoFrame>,
 }
 
-impl Drop for SendInstance {
+// The sender is shared with the metadata capture thread, which the SDK allows
+struct SendInstanceInner(NDIlib_send_instance_t);
+
+unsafe impl Send for SendInstanceInner {}
+unsafe impl Sync for SendInstanceInner {}
+
+impl Drop for SendInstanceInner {
     fn drop(&mut self) {
         unsafe {
-            NDIlib_send_destroy(self.instance);
+            NDIlib_send_destroy(self.0);
+        }
+    }
+}
+
+impl SendInstanceInner {
+    fn get_no_connections(&self, timeout_in_ms: u32) -> i32 {
+        unsafe { NDIlib_send_get_no_connections(self.0, timeout_in_ms) }
+    }
+
+    fn get_tally(&self, timeout_in_ms: u32) -> Option<(bool, bool)> {
+        let mut tally = NDIlib_tally_t {
+            on_program: false,
+            on_preview: false,
+        };
+
+        if unsafe { NDIlib_send_get_tally(self.0, &mut tally, timeout_in_ms) } {
+            Some((tally.on_program, tally.on_preview))
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for SendInstance {
+    fn drop(&mut self) {
+        self.flush_async();
+    }
+}
+
+/// Something that changed on a sender, as reported by the capture thread
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SenderEvent {
+    /// Metadata frame sent by a receiver
+    Metadata(String),
+    /// Current number of connected receivers
+    Connections(i32),
+    /// New `(on_program, on_preview)` tally state
+    Tally(bool, bool),
+}
+
+/// Thread capturing metadata sent by receivers and polling the tally and the number of
+/// connections, stopped when dropped
+pub struct CaptureThread {
+    shutdown: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Drop for CaptureThread {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
         }
     }
 }
@@ -22,27 +104,416 @@ impl Drop for SendInstance {
 impl SendInstance {
     pub fn send_video(&mut self, frame: NDISendVideoFrame) {
         unsafe {
-            NDIlib_send_send_video_v2(self.instance, &frame.instance);
+            NDIlib_send_send_video_v2(self.instance.0, &frame.instance);
+        }
+    }
+
+    pub fn send_video_async(&mut self, frame: NDISendVideoFrame) {
+        unsafe {
+            NDIlib_send_send_video_async_v2(self.instance.0, &frame.instance);
+        }
+        // The previous frame is released by the SDK now
+        self.async_frame = Some(frame);
+    }
+
+    pub fn flush_async(&mut self) {
+        if self.async_frame.is_some() {
+            unsafe {
+                NDIlib_send_send_video_async_v2(self.instance.0, null());
+            }
+            self.async_frame = None;
+        }
+    }
+
+    pub fn send_metadata(&mut self, data: &str, timecode: i64) -> Result<(), SendCreateError> {
+        if data.contains('\0') {
+            return Err(SendCreateError::InvalidMetadata);
+        }
+
+        let frame = MetadataFrame::new(timecode, Some(data));
+        unsafe {
+            NDIlib_send_send_metadata(self.instance.0, frame.as_ptr());
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the metadata sent to every newly connecting receiver with the defaults
+    /// identifying this plugin, followed by `extra` if given
+    pub fn set_connection_metadata(&mut self, extra: Option<&str>) -> Result<(), SendCreateError> {
+        unsafe {
+            NDIlib_send_clear_connection_metadata(self.instance.0);
+        }
+
+        for data in Some(DEFAULT_CONNECTION_METADATA.as_str())
+            .iter()
+            .chain(extra.iter())
+        {
+            if data.contains('\0') {
+                return Err(SendCreateError::InvalidMetadata);
+            }
+
+            let frame = MetadataFrame::new(NDIlib_send_timecode_synthesize, Some(data));
+            unsafe {
+                NDIlib_send_add_connection_metadata(self.instance.0, frame.as_ptr());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Number of connected receivers, waiting up to `timeout_in_ms` for one to connect if
+    /// there is none
+    pub fn get_no_connections(&mut self, timeout_in_ms: u32) -> i32 {
+        self.instance.get_no_connections(timeout_in_ms)
+    }
+
+    /// Returns the new `(on_program, on_preview)` tally state if it changed
+    pub fn get_tally(&mut self, timeout_in_ms: u32) -> Option<(bool, bool)> {
+        self.instance.get_tally(timeout_in_ms)
+    }
+
+    /// Calls `func` with every metadata frame received from a receiver and with the number of
+    /// connections and the tally state, which are polled about every 100ms, until the
+    /// returned handle is dropped
+    pub fn start_capture<F: Fn(SenderEvent) + Send + 'static>(&self, func: F) -> CaptureThread {
+        let instance = self.instance.clone();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let shutdown_clone = shutdown.clone();
+
+        let thread = thread::spawn(move || {
+            while !shutdown_clone.load(Ordering::SeqCst) {
+                func(SenderEvent::Connections(instance.get_no_connections(0)));
+                if let Some((on_program, on_preview)) = instance.get_tally(0) {
+                    func(SenderEvent::Tally(on_program, on_preview));
+                }
+
+                let mut frame = NDIlib_metadata_frame_t {
+                    length: 0,
+                    timecode: 0,
+                    p_data: null(),
+                };
+
+                let res = unsafe { NDIlib_send_capture(instance.0, &mut frame, 100) };
+                if res != NDIlib_frame_type_e::NDIlib_frame_type_metadata {
+                    continue;
+                }
+
+                let data = if frame.p_data.is_null() {
+                    None
+                } else {
+                    unsafe { Some(CStr::from_ptr(frame.p_data).to_string_lossy().into_owned()) }
+                };
+                unsafe {
+                    NDIlib_send_free_metadata(instance.0, &frame);
+                }
+
+                if let Some(data) = data {
+                    func(SenderEvent::Metadata(data));
+                }
+            }
+        });
+
+        CaptureThread {
+            shutdown,
+            thread: Some(thread),
         }
     }
 
     pub fn send_audio(&mut self, frame: NDISendAudioFrame) {
         unsafe {
-            NDIlib_util_send_send_audio_interleaved_16s(self.instance, &frame.instance);
+            NDIlib_send_send_audio_v2(self.instance.0, &frame.instance);
         }
     }
 }
 
+/// Send instance of a sink element together with the sender state reported to the
+/// application
+pub struct Sender {
+    cat: gst::DebugCategory,
+    state: Mutex<SenderState>,
+    // Shared with the capture thread, which reports changes as they happen
+    status: Arc<Mutex<SenderStatus>>,
+    unlocked: AtomicBool,
+}
+
+#[derive(Default)]
+struct SenderState {
+    sender: Option<SendInstance>,
+    capture_thread: Option<CaptureThread>,
+}
+
+#[derive(Default)]
+struct SenderStatus {
+    on_program: bool,
+    on_preview: bool,
+    connections: i32,
+}
+
+impl Sender {
+    pub fn new(cat: gst::DebugCategory) -> Self {
+        Self {
+            cat,
+            state: Mutex::new(SenderState::default()),
+            status: Arc::new(Mutex::new(SenderStatus::default())),
+            unlocked: AtomicBool::new(false),
+        }
+    }
+
+    /// Starts sending with `sender`, forwarding metadata received from receivers to the
+    /// application and upstream of `element` and reporting tally and connection changes
+    pub fn start(
+        &self,
+        element: &gst::Element,
+        mut sender: SendInstance,
+        connection_metadata: Option<&str>,
+    ) {
+        if let Err(err) = sender.set_connection_metadata(connection_metadata) {
+            gst_error!(self.cat, obj: element, "Failed to set connection metadata: {}", err);
+        }
+
+        let cat = self.cat;
+        let status = self.status.clone();
+        let element_weak = element.downgrade();
+        let capture_thread = sender.start_capture(move |event| {
+            let element = match element_weak.upgrade() {
+                Some(element) => element,
+                None => return,
+            };
+
+            match event {
+                SenderEvent::Metadata(data) => metadata_received(cat, &element, data),
+                SenderEvent::Connections(connections) => {
+                    update_connections(cat, &element, &status, connections)
+                }
+                SenderEvent::Tally(on_program, on_preview) => {
+                    update_tally(cat, &element, &status, on_program, on_preview)
+                }
+            }
+        });
+
+        let mut state = self.state.lock().unwrap();
+        state.sender = Some(sender);
+        state.capture_thread = Some(capture_thread);
+    }
+
+    pub fn stop(&self) {
+        // Stop capturing outside the lock as the capture thread might be waiting for it in a
+        // signal handler
+        let capture_thread = self.state.lock().unwrap().capture_thread.take();
+        drop(capture_thread);
+
+        *self.state.lock().unwrap() = SenderState::default();
+        *self.status.lock().unwrap() = SenderStatus::default();
+    }
+
+    /// Calls `func` with the send instance if there is one
+    pub fn with_instance<T, F: FnOnce(&mut SendInstance) -> T>(&self, func: F) -> Option<T> {
+        let mut state = self.state.lock().unwrap();
+        state.sender.as_mut().map(func)
+    }
+
+    /// Replaces the additional metadata sent to newly connecting receivers
+    pub fn set_connection_metadata(
+        &self,
+        element: &gst::Element,
+        connection_metadata: Option<&str>,
+    ) {
+        let res = self.with_instance(|sender| sender.set_connection_metadata(connection_metadata));
+        if let Some(Err(err)) = res {
+            gst_error!(self.cat, obj: element, "Failed to set connection metadata: {}", err);
+        }
+    }
+
+    pub fn on_program(&self) -> bool {
+        self.status.lock().unwrap().on_program
+    }
+
+    pub fn on_preview(&self) -> bool {
+        self.status.lock().unwrap().on_preview
+    }
+
+    pub fn connections(&self) -> i32 {
+        self.status.lock().unwrap().connections
+    }
+
+    /// Makes `wait_for_connection` return immediately until `unlock_stop` is called
+    pub fn unlock(&self) {
+        self.unlocked.store(true, Ordering::SeqCst);
+    }
+
+    pub fn unlock_stop(&self) {
+        self.unlocked.store(false, Ordering::SeqCst);
+    }
+
+    /// Whether `buffer` has to be dropped because `drop_without_connections` is set and no
+    /// receiver is connected
+    pub fn should_drop(
+        &self,
+        element: &gst::Element,
+        drop_without_connections: bool,
+        buffer: &gst::Buffer,
+    ) -> bool {
+        if !drop_without_connections || self.connections() > 0 {
+            return false;
+        }
+
+        // The capture thread only polls every now and then, so check again before dropping
+        // anything. The change itself is still reported from the capture thread.
+        let connections = self
+            .with_instance(|sender| sender.get_no_connections(0))
+            .unwrap_or(0);
+        if connections > 0 {
+            return false;
+        }
+
+        gst_trace!(self.cat, obj: element, "No receivers connected, dropping {:?}", buffer);
+        true
+    }
+
+    /// Sends an NDI metadata frame to all connected receivers
+    pub fn send_metadata(&self, element: &gst::Element, data: &str) -> bool {
+        gst_debug!(self.cat, obj: element, "Sending metadata {}", data);
+
+        let res = self
+            .with_instance(|sender| sender.send_metadata(data, NDIlib_send_timecode_synthesize));
+        match res {
+            Some(Ok(())) => true,
+            Some(Err(err)) => {
+                gst_error!(self.cat, obj: element, "Failed to send metadata: {}", err);
+                false
+            }
+            None => {
+                gst_warning!(self.cat, obj: element, "Can't send metadata without sender");
+                false
+            }
+        }
+    }
+
+    /// Waits up to `timeout` ms for a receiver to connect. This waits in small steps so that
+    /// unlocking is not delayed by the whole timeout.
+    pub fn wait_for_connection(
+        &self,
+        element: &gst::Element,
+        timeout: u32,
+    ) -> Result<(), gst::FlowError> {
+        let start = time::Instant::now();
+
+        loop {
+            if self.unlocked.load(Ordering::SeqCst) {
+                return Err(gst::FlowError::Flushing);
+            }
+
+            let elapsed = start.elapsed();
+            let elapsed = (elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis())) as u32;
+            if elapsed >= timeout {
+                gst_debug!(self.cat, obj: element, "No receiver connected after {}ms", timeout);
+                return Ok(());
+            }
+
+            let step = cmp::min(timeout - elapsed, 100);
+            let connections = self
+                .with_instance(|sender| sender.get_no_connections(step))
+                .unwrap_or(0);
+            update_connections(self.cat, element, &self.status, connections);
+            if connections > 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Stores the number of connected receivers and notifies the application about changes via
+// the `connections` property and an element message
+fn update_connections(
+    cat: gst::DebugCategory,
+    element: &gst::Element,
+    status: &Mutex<SenderStatus>,
+    connections: i32,
+) {
+    {
+        let mut status = status.lock().unwrap();
+        if status.connections == connections {
+            return;
+        }
+        status.connections = connections;
+    }
+
+    gst_debug!(cat, obj: element, "Now {} receivers connected", connections);
+
+    element.notify("connections");
+
+    let s = gst::Structure::new("ndi-connections", &[("connections", &connections)]);
+    let _ = element.post_message(&gst::Message::new_element(s).src(Some(element)).build());
+}
+
+// Stores the tally state and notifies the application about changes via the `on-program` and
+// `on-preview` properties, the `tally-changed` signal and an element message
+fn update_tally(
+    cat: gst::DebugCategory,
+    element: &gst::Element,
+    status: &Mutex<SenderStatus>,
+    on_program: bool,
+    on_preview: bool,
+) {
+    {
+        let mut status = status.lock().unwrap();
+        if (status.on_program, status.on_preview) == (on_program, on_preview) {
+            return;
+        }
+        status.on_program = on_program;
+        status.on_preview = on_preview;
+    }
+
+    gst_debug!(
+        cat,
+        obj: element,
+        "Tally changed to program {} preview {}",
+        on_program,
+        on_preview
+    );
+
+    element.notify("on-program");
+    element.notify("on-preview");
+    let _ = element.emit("tally-changed", &[&on_program, &on_preview]);
+
+    let s = gst::Structure::new(
+        "ndi-tally",
+        &[("on-program", &on_program), ("on-preview", &on_preview)],
+    );
+    let _ = element.post_message(&gst::Message::new_element(s).src(Some(element)).build());
+}
+
+// Forwards metadata received from a receiver to the application and upstream
+fn metadata_received(cat: gst::DebugCategory, element: &gst::Element, data: String) {
+    gst_debug!(cat, obj: element, "Received metadata {}", data);
+
+    let _ = element.emit("metadata-received", &[&data]);
+
+    let s = gst::Structure::new("ndi-metadata", &[("data", &data)]);
+    let _ = element.post_message(
+        &gst::Message::new_element(s.clone())
+            .src(Some(element))
+            .build(),
+    );
+
+    let event = gst::Event::new_custom_upstream(s).build();
+    for pad in element.get_sink_pads() {
+        pad.push_event(event.clone());
+    }
+}
+
 pub struct NDISendVideoFrameBuilder {
     instance: NDIlib_video_frame_v2_t,
     metadata: Option<String>,
     data: Vec<u8>,
+    frame: Option<gst_video::VideoFrame<gst_video::video_frame::Readable>>,
 }
 
 pub struct NDISendAudioFrameBuilder {
-    instance: NDIlib_audio_frame_interleaved_16s_t,
+    instance: NDIlib_audio_frame_v2_t,
     metadata: Option<String>,
-    data: Vec<u8>,
+    data: Vec<f32>,
 }
 
 
@@ -53,8 +524,37 @@ impl NDISendVideoFrameBuilder {
         self
     }
 
-    pub fn with_format(mut self, video_format: gst_video::VideoFormat) -> Self {
-        //TODO: fix unreachable_patterns warning
+    /// Sends directly from the mapped frame, which must already be laid out the way NDI
+    /// expects it
+    pub fn with_video_frame(
+        mut self,
+        frame: gst_video::VideoFrame<gst_video::video_frame::Readable>,
+    ) -> Self {
+        self.instance.line_stride_in_bytes = frame.plane_stride()[0];
+        self.frame = Some(frame);
+        self
+    }
+
+    pub fn with_timecode(mut self, timecode: i64) -> Self {
+        self.instance.timecode = timecode;
+        self
+    }
+
+    pub fn with_frame_rate(mut self, numerator: i32, denominator: i32) -> Self {
+        self.instance.frame_rate_N = numerator;
+        self.instance.frame_rate_D = denominator;
+        self
+    }
+
+    pub fn with_picture_aspect_ratio(mut self, picture_aspect_ratio: f32) -> Self {
+        self.instance.picture_aspect_ratio = picture_aspect_ratio;
+        self
+    }
+
+    pub fn with_format(
+        mut self,
+        video_format: gst_video::VideoFormat,
+    ) -> Result<Self, SendCreateError> {
         let format = match video_format {
             gst_video::VideoFormat::Uyvy => ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_UYVY,
             gst_video::VideoFormat::I420 => ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_YV12,
@@ -64,23 +564,46 @@ impl NDISendVideoFrameBuilder {
             gst_video::VideoFormat::Bgrx => ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_BGRX,
             gst_video::VideoFormat::Rgba => ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_RGBA,
             gst_video::VideoFormat::Rgbx => ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_RGBX,
-            gst_video::VideoFormat::Uyvy => ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_UYVA,
-            _ => panic!("Unkown format"),
+            gst_video::VideoFormat::Ayuv => ndisys::NDIlib_FourCC_type_e::NDIlib_FourCC_type_UYVA,
+            _ => return Err(SendCreateError::UnsupportedVideoFormat(video_format)),
         };
 
         self.instance.FourCC = format;
+        Ok(self)
+    }
+
+    pub fn with_metadata(mut self, metadata: String) -> Self {
+        self.metadata = Some(metadata);
         self
     }
 
     pub fn build(self) -> Result<NDISendVideoFrame, SendCreateError> {
+        let metadata = self
+            .metadata
+            .map(CString::new)
+            .transpose()
+            .map_err(|_| SendCreateError::InvalidMetadata)?;
+
         let mut res = NDISendVideoFrame {
             instance: self.instance,
-            metadata: self.metadata,
+            metadata,
             data: self.data,
+            frame: self.frame,
         };
 
-        res.data.resize((res.instance.line_stride_in_bytes * res.instance.yres) as usize, 0);
-        res.instance.p_data = res.data.as_mut_ptr() as *const c_char;
+        res.instance.p_metadata = res.metadata.as_ref().map(|s| s.as_ptr()).unwrap_or(null());
+
+        if let Some(ref frame) = res.frame {
+            let data = frame.plane_data(0).ok_or(SendCreateError::MapFailed)?;
+            res.instance.p_data = data.as_ptr() as *const c_char;
+        } else {
+            // Planar formats carry their chroma planes after the first plane
+            let min_size = (res.instance.line_stride_in_bytes * res.instance.yres) as usize;
+            if res.data.len() < min_size {
+                res.data.resize(min_size, 0);
+            }
+            res.instance.p_data = res.data.as_mut_ptr() as *const c_char;
+        }
 
         Ok(res)
     }
@@ -104,24 +627,51 @@ pub fn create_ndi_send_video_frame(width: i32, height: i32, frame_type: NDIlib_f
         },
         metadata: None,
         data: vec![],
+        frame: None,
     }
 }
 
 impl NDISendAudioFrameBuilder {
-    pub fn with_data(mut self, data: Vec<u8>) -> Self {
+    pub fn with_timecode(mut self, timecode: i64) -> Self {
+        self.instance.timecode = timecode;
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: String) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Planar float data, one plane of `no_samples` samples per channel
+    pub fn with_data(mut self, data: Vec<f32>) -> Self {
         self.data = data;
         self
     }
 
     pub fn build(self) -> Result<NDISendAudioFrame, SendCreateError> {
+        let metadata = self
+            .metadata
+            .map(CString::new)
+            .transpose()
+            .map_err(|_| SendCreateError::InvalidMetadata)?;
+
         let mut res = NDISendAudioFrame {
             instance: self.instance,
-            metadata: self.metadata,
+            metadata,
             data: self.data,
         };
 
-        res.instance.no_samples = (res.data.len() / res.instance.no_channels as usize / size_of::<i16>()) as i32;
-        res.instance.p_data = res.data.as_mut_ptr() as *mut i16;
+        res.instance.p_metadata = res.metadata.as_ref().map(|s| s.as_ptr()).unwrap_or(null());
+
+        if res.instance.no_channels <= 0 || res.data.len() % res.instance.no_channels as usize != 0
+        {
+            return Err(SendCreateError::InvalidData);
+        }
+
+        let no_samples = res.data.len() / res.instance.no_channels as usize;
+        res.instance.no_samples = no_samples as i32;
+        res.instance.channel_stride_in_bytes = (no_samples * size_of::<f32>()) as i32;
+        res.instance.p_data = res.data.as_ptr();
 
         Ok(res)
     }
@@ -129,49 +679,533 @@ impl NDISendAudioFrameBuilder {
 
 pub fn create_ndi_send_audio_frame(sample_rate: i32, no_channels: i32) -> NDISendAudioFrameBuilder {
     NDISendAudioFrameBuilder {
-        instance: NDIlib_audio_frame_interleaved_16s_t {
+        instance: NDIlib_audio_frame_v2_t {
             sample_rate: sample_rate,
             no_channels: no_channels,
             no_samples: 0,
-            p_data: null_mut(),
-            reference_level: 0,
             timecode: NDIlib_send_timecode_synthesize,
+            p_data: null(),
+            channel_stride_in_bytes: 0,
+            p_metadata: null(),
+            timestamp: 0,
         },
         metadata: None,
         data: vec![],
     }
 }   
 
-#[derive(Debug)]
 pub struct NDISendVideoFrame {
     instance: NDIlib_video_frame_v2_t,
-    metadata: Option<String>,
+    metadata: Option<CString>,
     data: Vec<u8>,
+    frame: Option<gst_video::VideoFrame<gst_video::video_frame::Readable>>,
 }
 
 #[derive(Debug)]
 pub struct NDISendAudioFrame {
-    instance: NDIlib_audio_frame_interleaved_16s_t,
-    metadata: Option<String>,
-    data: Vec<u8>,
+    instance: NDIlib_audio_frame_v2_t,
+    metadata: Option<CString>,
+    data: Vec<f32>,
+}
+
+/// Timecode in 100ns units for the buffer according to `mode`, or
+/// `NDIlib_send_timecode_synthesize` if it can't be determined
+pub fn get_timecode(
+    mode: TimecodeMode,
+    segment: &gst::FormattedSegment<gst::ClockTime>,
+    buffer: &gst::BufferRef,
+) -> i64 {
+    let timecode = match mode {
+        TimecodeMode::Synthesize => gst::CLOCK_TIME_NONE,
+        TimecodeMode::RunningTime => segment.to_running_time(buffer.get_pts()),
+        TimecodeMode::TimeCodeMeta => buffer
+            .get_meta::<gst_video::VideoTimeCodeMeta>()
+            .map(|meta| gst::ClockTime::from_nseconds(meta.get_tc().nsec_since_daily_jam()))
+            .unwrap_or(gst::CLOCK_TIME_NONE),
+        #[cfg(feature = "reference-timestamps")]
+        TimecodeMode::ReferenceTimestamp => buffer
+            .iter_meta::<gst::ReferenceTimestampMeta>()
+            .find(|meta| {
+                meta.get_reference()
+                    .map(|caps| caps.can_intersect(&*TIMECODE_CAPS))
+                    .unwrap_or(false)
+            })
+            .map(|meta| meta.get_timestamp())
+            .unwrap_or(gst::CLOCK_TIME_NONE),
+        #[cfg(not(feature = "reference-timestamps"))]
+        TimecodeMode::ReferenceTimestamp => gst::CLOCK_TIME_NONE,
+    };
+
+    timecode
+        .nseconds()
+        .map(|timecode| (timecode / 100) as i64)
+        .unwrap_or(NDIlib_send_timecode_synthesize)
+}
+
+pub fn create_ndi_send_video_frame_from_buffer(
+    info: &gst_video::VideoInfo,
+    buffer: &gst::BufferRef,
+    timecode: i64,
+) -> Result<NDISendVideoFrame, SendCreateError> {
+    let in_frame = gst_video::VideoFrameRef::from_buffer_ref_readable(buffer, info)
+        .ok_or(SendCreateError::MapFailed)?;
+    let (data, line_stride) = pack_video_frame(&in_frame)?;
+
+    create_builder_for_buffer(info, buffer, in_frame.width(), in_frame.height(), timecode)?
+        .with_data(data, line_stride as i32)
+        .build()
+}
+
+/// Like `create_ndi_send_video_frame_from_buffer` but keeps the buffer mapped and sends
+/// from its memory if the layout allows it, for use with `SendInstance::send_video_async`
+pub fn create_ndi_send_video_frame_from_owned_buffer(
+    info: &gst_video::VideoInfo,
+    buffer: gst::Buffer,
+    timecode: i64,
+) -> Result<NDISendVideoFrame, SendCreateError> {
+    let in_frame = gst_video::VideoFrame::from_buffer_readable(buffer, info)
+        .map_err(|_| SendCreateError::MapFailed)?;
+
+    if !is_ndi_layout(&in_frame) {
+        return create_ndi_send_video_frame_from_buffer(info, in_frame.buffer(), timecode);
+    }
+
+    create_builder_for_buffer(
+        info,
+        in_frame.buffer(),
+        in_frame.width(),
+        in_frame.height(),
+        timecode,
+    )?
+    .with_video_frame(in_frame)
+    .build()
+}
+
+fn create_builder_for_buffer(
+    info: &gst_video::VideoInfo,
+    buffer: &gst::BufferRef,
+    width: u32,
+    height: u32,
+    timecode: i64,
+) -> Result<NDISendVideoFrameBuilder, SendCreateError> {
+    let frame_format_type = get_frame_format_type(info, buffer);
+
+    // NDI has no notion of variable framerates, so derive one from the buffer duration
+    // in that case and otherwise signal 0/1 like GStreamer does
+    let fps = info.fps();
+    let (fps_n, fps_d) = if *fps.numer() > 0 {
+        (*fps.numer(), *fps.denom())
+    } else {
+        match buffer.get_duration().nseconds() {
+            Some(duration) if duration > 0 && duration <= i32::MAX as u64 => {
+                (gst::SECOND_VAL as i32, duration as i32)
+            }
+            _ => (0, 1),
+        }
+    };
+
+    let par = info.par();
+    let picture_aspect_ratio = (info.width() as f64 * *par.numer() as f64)
+        / (info.height() as f64 * *par.denom() as f64);
+
+    let builder = create_ndi_send_video_frame(width as i32, height as i32, frame_format_type)
+        .with_timecode(timecode)
+        .with_frame_rate(fps_n, fps_d)
+        .with_picture_aspect_ratio(picture_aspect_ratio as f32)
+        .with_format(info.format())?;
+
+    match buffer.get_meta::<NdiFrameMetadataMeta>() {
+        Some(meta) => Ok(builder.with_metadata(meta.get_metadata().into_owned())),
+        None => Ok(builder),
+    }
+}
+
+// Checks if the planes of the mapped frame are already contiguous in the way described
+// in `pack_video_frame`
+fn is_ndi_layout(frame: &gst_video::VideoFrame<gst_video::video_frame::Readable>) -> bool {
+    let height = frame.height() as usize;
+    let chroma_height = (height + 1) / 2;
+    let strides = frame.plane_stride();
+
+    let plane_ptr = |idx: u32| frame.plane_data(idx).map(|data| data.as_ptr() as usize);
+
+    match frame.format() {
+        gst_video::VideoFormat::Uyvy
+        | gst_video::VideoFormat::Bgra
+        | gst_video::VideoFormat::Bgrx
+        | gst_video::VideoFormat::Rgba
+        | gst_video::VideoFormat::Rgbx => true,
+        gst_video::VideoFormat::Nv12 => match (plane_ptr(0), plane_ptr(1)) {
+            (Some(luma), Some(chroma)) => {
+                strides[1] == strides[0] && chroma == luma + strides[0] as usize * height
+            }
+            _ => false,
+        },
+        gst_video::VideoFormat::Yv12 | gst_video::VideoFormat::I420 => {
+            match (plane_ptr(0), plane_ptr(1), plane_ptr(2)) {
+                (Some(luma), Some(chroma1), Some(chroma2)) => {
+                    strides[0] % 2 == 0
+                        && strides[1] == strides[0] / 2
+                        && strides[2] == strides[1]
+                        && chroma1 == luma + strides[0] as usize * height
+                        && chroma2 == chroma1 + strides[1] as usize * chroma_height
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+fn copy_plane(
+    dest: &mut [u8],
+    dest_stride: usize,
+    src: &[u8],
+    src_stride: usize,
+    line_bytes: usize,
+    lines: usize,
+) {
+    for (dest, src) in dest
+        .chunks_mut(dest_stride)
+        .zip(src.chunks(src_stride))
+        .take(lines)
+    {
+        dest[..line_bytes].copy_from_slice(&src[..line_bytes]);
+    }
+}
+
+// NDI expects all planes of a frame in one contiguous allocation: the first plane with
+// `line_stride_in_bytes` per line, directly followed by the chroma plane(s) of (yres + 1) / 2
+// lines each. For NV12 the chroma stride is the same as the luma stride, for the
+// three-plane formats it is half of it. AYUV is converted to UYVA, which is UYVY followed by
+// an alpha plane with a stride of xres.
+fn pack_video_frame(
+    in_frame: &gst_video::VideoFrameRef<&gst::BufferRef>,
+) -> Result<(Vec<u8>, usize), SendCreateError> {
+    let width = in_frame.width() as usize;
+    let height = in_frame.height() as usize;
+    let chroma_width = (width + 1) / 2;
+    let chroma_height = (height + 1) / 2;
+
+    let plane = |idx: u32| -> Result<(&[u8], usize), SendCreateError> {
+        let data = in_frame.plane_data(idx).ok_or(SendCreateError::MapFailed)?;
+        Ok((data, in_frame.plane_stride()[idx as usize] as usize))
+    };
+
+    match in_frame.format() {
+        gst_video::VideoFormat::Uyvy
+        | gst_video::VideoFormat::Bgra
+        | gst_video::VideoFormat::Bgrx
+        | gst_video::VideoFormat::Rgba
+        | gst_video::VideoFormat::Rgbx => {
+            // UYVY lines always contain complete pairs of pixels
+            let line_bytes = if in_frame.format() == gst_video::VideoFormat::Uyvy {
+                4 * chroma_width
+            } else {
+                4 * width
+            };
+
+            let mut data = vec![0; line_bytes * height];
+            let (src, src_stride) = plane(0)?;
+            copy_plane(&mut data, line_bytes, src, src_stride, line_bytes, height);
+
+            Ok((data, line_bytes))
+        }
+        gst_video::VideoFormat::Nv12 => {
+            let stride = 2 * chroma_width;
+
+            let mut data = vec![0; stride * (height + chroma_height)];
+            let (luma, chroma) = data.split_at_mut(stride * height);
+
+            let (src, src_stride) = plane(0)?;
+            copy_plane(luma, stride, src, src_stride, width, height);
+            let (src, src_stride) = plane(1)?;
+            copy_plane(
+                chroma,
+                stride,
+                src,
+                src_stride,
+                2 * chroma_width,
+                chroma_height,
+            );
+
+            Ok((data, stride))
+        }
+        gst_video::VideoFormat::Yv12 | gst_video::VideoFormat::I420 => {
+            let stride = 2 * chroma_width;
+            let chroma_stride = chroma_width;
+
+            let mut data = vec![0; stride * height + 2 * chroma_stride * chroma_height];
+            let (luma, chroma) = data.split_at_mut(stride * height);
+            let (chroma1, chroma2) = chroma.split_at_mut(chroma_stride * chroma_height);
+
+            let (src, src_stride) = plane(0)?;
+            copy_plane(luma, stride, src, src_stride, width, height);
+            let (src, src_stride) = plane(1)?;
+            copy_plane(
+                chroma1,
+                chroma_stride,
+                src,
+                src_stride,
+                chroma_width,
+                chroma_height,
+            );
+            let (src, src_stride) = plane(2)?;
+            copy_plane(
+                chroma2,
+                chroma_stride,
+                src,
+                src_stride,
+                chroma_width,
+                chroma_height,
+            );
+
+            Ok((data, stride))
+        }
+        gst_video::VideoFormat::Ayuv => {
+            let stride = 4 * chroma_width;
+
+            let mut data = vec![0; stride * height + width * height];
+            let (uyvy, alpha) = data.split_at_mut(stride * height);
+
+            let (src, src_stride) = plane(0)?;
+            for ((uyvy, alpha), src) in uyvy
+                .chunks_exact_mut(stride)
+                .zip(alpha.chunks_exact_mut(width))
+                .zip(src.chunks(src_stride))
+                .take(height)
+            {
+                let src = &src[..4 * width];
+                for (x, (alpha, ayuv)) in alpha.iter_mut().zip(src.chunks_exact(4)).enumerate() {
+                    *alpha = ayuv[0];
+                    uyvy[(x / 2) * 4 + 1 + 2 * (x % 2)] = ayuv[1];
+                }
+
+                // Average the chroma of both pixels of each pair
+                for (uyvy, ayuv) in uyvy.chunks_exact_mut(4).zip(src.chunks(8)) {
+                    let (u, v) = if ayuv.len() == 8 {
+                        (
+                            ((ayuv[2] as u16 + ayuv[6] as u16 + 1) / 2) as u8,
+                            ((ayuv[3] as u16 + ayuv[7] as u16 + 1) / 2) as u8,
+                        )
+                    } else {
+                        (ayuv[2], ayuv[3])
+                    };
+                    uyvy[0] = u;
+                    uyvy[2] = v;
+                }
+            }
+
+            Ok((data, stride))
+        }
+        format => Err(SendCreateError::UnsupportedVideoFormat(format)),
+    }
+}
+
+/// XML data of a custom `ndi-metadata` event, which carries it in its `data` field
+pub fn get_metadata_from_event(event: &gst::EventRef) -> Option<String> {
+    match event.view() {
+        gst::EventView::CustomDownstream(..) | gst::EventView::CustomDownstreamOob(..) => {
+            let s = event.get_structure()?;
+            if s.get_name() == "ndi-metadata" {
+                s.get::<String>("data")
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+fn get_frame_format_type(
+    info: &gst_video::VideoInfo,
+    buffer: &gst::BufferRef,
+) -> NDIlib_frame_format_type_e {
+    let flags = buffer.get_video_flags();
+
+    match info.interlace_mode() {
+        gst_video::VideoInterlaceMode::Progressive => {
+            NDIlib_frame_format_type_e::NDIlib_frame_format_type_progressive
+        }
+        gst_video::VideoInterlaceMode::Interleaved => {
+            NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved
+        }
+        gst_video::VideoInterlaceMode::Mixed
+            if flags.contains(gst_video::VideoBufferFlags::INTERLACED) =>
+        {
+            NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved
+        }
+        #[cfg(feature = "interlaced-fields")]
+        gst_video::VideoInterlaceMode::Alternate
+            if flags.contains(gst_video::VideoBufferFlags::BOTTOM_FIELD) =>
+        {
+            NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_1
+        }
+        #[cfg(feature = "interlaced-fields")]
+        gst_video::VideoInterlaceMode::Alternate => {
+            NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_0
+        }
+        _ => NDIlib_frame_format_type_e::NDIlib_frame_format_type_progressive,
+    }
+}
+
+/// Returns the number of samples per channel and the byte offset of each plane from the
+/// `GstAudioMeta` of a non-interleaved buffer, if it has one.
+#[cfg(feature = "audio-meta")]
+fn get_audio_meta_planes(buffer: &gst::BufferRef, channels: usize) -> Option<(usize, Vec<usize>)> {
+    unsafe {
+        let meta = gst_audio_sys::gst_buffer_get_audio_meta(buffer.as_ptr() as *mut _);
+        if meta.is_null() {
+            return None;
+        }
+
+        let meta = &*meta;
+        if meta.offsets.is_null() || meta.info.channels as usize != channels {
+            return None;
+        }
+
+        let offsets = std::slice::from_raw_parts(meta.offsets, channels);
+        Some((meta.samples, offsets.to_vec()))
+    }
+}
+
+#[cfg(not(feature = "audio-meta"))]
+fn get_audio_meta_planes(
+    _buffer: &gst::BufferRef,
+    _channels: usize,
+) -> Option<(usize, Vec<usize>)> {
+    None
+}
+
+pub fn create_ndi_send_audio_frame_from_buffer(
+    info: &gst_audio::AudioInfo,
+    buffer: &gst::BufferRef,
+    timecode: i64,
+) -> Result<NDISendAudioFrame, SendCreateError> {
+    let map = buffer.map_readable().ok_or(SendCreateError::MapFailed)?;
+    let data = map.as_slice();
+
+    let channels = info.channels() as usize;
+    let bpf = info.bpf() as usize;
+    if channels == 0 || bpf == 0 {
+        return Err(SendCreateError::InvalidData);
+    }
+
+    // Non-interleaved buffers describe where each plane starts in their audio meta. Only
+    // without one the planes are assumed to follow each other without any padding
+    let audio_meta = if info.layout() == gst_audio::AudioLayout::NonInterleaved {
+        get_audio_meta_planes(buffer, channels)
+    } else {
+        None
+    };
+    let (samples, plane_offsets) = match audio_meta {
+        Some(planes) => planes,
+        None => {
+            if data.len() % bpf != 0 {
+                return Err(SendCreateError::InvalidData);
+            }
+            let samples = data.len() / bpf;
+            let plane_size = samples * (bpf / channels);
+            (samples, (0..channels).map(|c| c * plane_size).collect())
+        }
+    };
+
+    // NDI wants planar float samples, so convert everything else here
+    let mut planar = vec![0.0f32; samples * channels];
+    match (info.format(), info.layout()) {
+        (gst_audio::AUDIO_FORMAT_F32, gst_audio::AudioLayout::NonInterleaved) => {
+            for (channel, offset) in plane_offsets.into_iter().enumerate() {
+                let plane = &mut planar[channel * samples..(channel + 1) * samples];
+                let src = data
+                    .get(offset..offset + samples * 4)
+                    .ok_or(SendCreateError::InvalidData)?;
+                for (sample, dst) in src.chunks_exact(4).zip(plane.iter_mut()) {
+                    *dst = f32::from_ne_bytes([sample[0], sample[1], sample[2], sample[3]]);
+                }
+            }
+        }
+        (gst_audio::AUDIO_FORMAT_F32, gst_audio::AudioLayout::Interleaved) => {
+            for (i, sample) in data.chunks_exact(4).enumerate() {
+                planar[(i % channels) * samples + i / channels] =
+                    f32::from_ne_bytes([sample[0], sample[1], sample[2], sample[3]]);
+            }
+        }
+        (gst_audio::AUDIO_FORMAT_S32, gst_audio::AudioLayout::Interleaved) => {
+            for (i, sample) in data.chunks_exact(4).enumerate() {
+                let sample = i32::from_ne_bytes([sample[0], sample[1], sample[2], sample[3]]);
+                planar[(i % channels) * samples + i / channels] =
+                    sample as f32 / -(::std::i32::MIN as f32);
+            }
+        }
+        (gst_audio::AUDIO_FORMAT_S16, gst_audio::AudioLayout::Interleaved) => {
+            for (i, sample) in data.chunks_exact(2).enumerate() {
+                let sample = i16::from_ne_bytes([sample[0], sample[1]]);
+                planar[(i % channels) * samples + i / channels] =
+                    f32::from(sample) / -f32::from(::std::i16::MIN);
+            }
+        }
+        (format, _) => return Err(SendCreateError::UnsupportedAudioFormat(format)),
+    }
+
+    let builder = create_ndi_send_audio_frame(info.rate() as i32, info.channels() as i32)
+        .with_timecode(timecode)
+        .with_data(planar);
+
+    match buffer.get_meta::<NdiFrameMetadataMeta>() {
+        Some(meta) => builder.with_metadata(meta.get_metadata().into_owned()),
+        None => builder,
+    }
+    .build()
 }
 
 #[derive(Debug)]
 pub enum SendCreateError {
     InvalidName,
+    InvalidGroups,
+    InvalidMetadata,
+    UnsupportedVideoFormat(gst_video::VideoFormat),
+    UnsupportedAudioFormat(gst_audio::AudioFormat),
+    MapFailed,
+    InvalidData,
     Failed,
 }
 
+impl fmt::Display for SendCreateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendCreateError::InvalidName => write!(f, "NDI name contains a NUL byte"),
+            SendCreateError::InvalidGroups => write!(f, "NDI groups contain a NUL byte"),
+            SendCreateError::InvalidMetadata => write!(f, "Metadata contains a NUL byte"),
+            SendCreateError::UnsupportedVideoFormat(format) => {
+                write!(f, "Unsupported video format {}", format)
+            }
+            SendCreateError::UnsupportedAudioFormat(format) => {
+                write!(f, "Unsupported audio format {:?}", format)
+            }
+            SendCreateError::MapFailed => write!(f, "Failed to map buffer"),
+            SendCreateError::InvalidData => write!(f, "Buffer size does not match the format"),
+            SendCreateError::Failed => write!(f, "NDI SDK call failed"),
+        }
+    }
+}
+
+impl error::Error for SendCreateError {}
+
 pub fn create_send_instance(
     name: String,
+    groups: Option<&str>,
     clock_video: bool,
     clock_audio: bool,
 ) -> Result<SendInstance, SendCreateError> {
     let name2 = CString::new(name.as_bytes()).map_err(|_| SendCreateError::InvalidName)?;
+    let groups = groups
+        .map(CString::new)
+        .transpose()
+        .map_err(|_| SendCreateError::InvalidGroups)?;
 
     let props = NDIlib_send_create_t {
         p_ndi_name: name2.as_ptr(),
-        p_groups: null(),
+        p_groups: groups.as_ref().map(|s| s.as_ptr()).unwrap_or(null()),
         clock_video,
         clock_audio,
     };
@@ -182,7 +1216,242 @@ pub fn create_send_instance(
         Err(SendCreateError::Failed)
     } else {
         Ok(SendInstance {
-            instance,
+            instance: Arc::new(SendInstanceInner(instance)),
+            async_frame: None,
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(plane: usize, line: usize, x: usize) -> u8 {
+        (plane * 71 + line * 13 + x * 3 + 1) as u8
+    }
+
+    fn plane_height(plane: usize, height: usize) -> usize {
+        if plane == 0 {
+            height
+        } else {
+            (height + 1) / 2
+        }
+    }
+
+    // Creates a frame with the given strides and some padding between the planes, with every
+    // byte of each line (including the padding at the end) filled from `pattern()`
+    fn create_frame(
+        format: gst_video::VideoFormat,
+        width: u32,
+        height: u32,
+        strides: &[i32],
+    ) -> (gst::Buffer, gst_video::VideoInfo) {
+        let mut offsets = vec![];
+        let mut size = 0;
+        for (plane, stride) in strides.iter().enumerate() {
+            offsets.push(size);
+            size += *stride as usize * plane_height(plane, height as usize) + 16;
+        }
+
+        let info = gst_video::VideoInfo::new(format, width, height)
+            .offset(&offsets)
+            .stride(strides)
+            .build()
+            .unwrap();
+
+        let mut data = vec![0xee; size.max(info.size())];
+        for (plane, stride) in strides.iter().enumerate() {
+            let stride = *stride as usize;
+            for line in 0..plane_height(plane, height as usize) {
+                for x in 0..stride {
+                    data[offsets[plane] + line * stride + x] = pattern(plane, line, x);
+                }
+            }
+        }
+
+        (gst::Buffer::from_mut_slice(data), info)
+    }
+
+    fn pack(buffer: &gst::Buffer, info: &gst_video::VideoInfo) -> (Vec<u8>, usize) {
+        let frame =
+            gst_video::VideoFrameRef::from_buffer_ref_readable(buffer.as_ref(), info).unwrap();
+        pack_video_frame(&frame).unwrap()
+    }
+
+    fn check_plane(
+        data: &[u8],
+        plane: usize,
+        offset: usize,
+        stride: usize,
+        line_bytes: usize,
+        lines: usize,
+    ) {
+        for line in 0..lines {
+            for x in 0..stride {
+                let expected = if x < line_bytes {
+                    pattern(plane, line, x)
+                } else {
+                    0
+                };
+                assert_eq!(
+                    data[offset + line * stride + x],
+                    expected,
+                    "plane {} line {} byte {}",
+                    plane,
+                    line,
+                    x
+                );
+            }
+        }
+    }
+
+    fn check_three_plane(format: gst_video::VideoFormat, strides: &[i32]) {
+        gst::init().unwrap();
+
+        let (buffer, info) = create_frame(format, 5, 3, strides);
+        let (data, stride) = pack(&buffer, &info);
+
+        // Chroma planes are 3x2, the luma stride is twice the chroma stride
+        assert_eq!(stride, 6);
+        assert_eq!(data.len(), 6 * 3 + 2 * 3 * 2);
+        check_plane(&data, 0, 0, 6, 5, 3);
+        check_plane(&data, 1, 18, 3, 3, 2);
+        check_plane(&data, 2, 24, 3, 3, 2);
+    }
+
+    #[test]
+    fn pack_i420() {
+        check_three_plane(gst_video::VideoFormat::I420, &[16, 8, 8]);
+    }
+
+    #[test]
+    fn pack_yv12() {
+        check_three_plane(gst_video::VideoFormat::Yv12, &[12, 12, 8]);
+    }
+
+    #[test]
+    fn pack_nv12() {
+        gst::init().unwrap();
+
+        let (buffer, info) = create_frame(gst_video::VideoFormat::Nv12, 5, 3, &[16, 12]);
+        let (data, stride) = pack(&buffer, &info);
+
+        // One interleaved chroma plane of 3x2 pairs with the luma stride
+        assert_eq!(stride, 6);
+        assert_eq!(data.len(), 6 * 3 + 6 * 2);
+        check_plane(&data, 0, 0, 6, 5, 3);
+        check_plane(&data, 1, 18, 6, 6, 2);
+    }
+
+    #[test]
+    fn pack_uyvy() {
+        gst::init().unwrap();
+
+        let (buffer, info) = create_frame(gst_video::VideoFormat::Uyvy, 5, 3, &[24]);
+        let (data, stride) = pack(&buffer, &info);
+
+        assert_eq!(stride, 12);
+        assert_eq!(data.len(), 12 * 3);
+        check_plane(&data, 0, 0, 12, 12, 3);
+    }
+
+    #[test]
+    fn pack_uyva() {
+        gst::init().unwrap();
+
+        let (buffer, info) = create_frame(gst_video::VideoFormat::Ayuv, 5, 3, &[32]);
+        let (data, stride) = pack(&buffer, &info);
+
+        // UYVY plane of 3 pairs per line followed by a 5x3 alpha plane
+        assert_eq!(stride, 12);
+        assert_eq!(data.len(), 12 * 3 + 5 * 3);
+
+        let avg = |a: u8, b: u8| ((a as u16 + b as u16 + 1) / 2) as u8;
+        for line in 0..3 {
+            let uyvy = &data[line * 12..(line + 1) * 12];
+            let alpha = &data[36 + line * 5..36 + (line + 1) * 5];
+
+            for x in 0..5 {
+                assert_eq!(alpha[x], pattern(0, line, 4 * x));
+                assert_eq!(
+                    uyvy[(x / 2) * 4 + 1 + 2 * (x % 2)],
+                    pattern(0, line, 4 * x + 1)
+                );
+            }
+            // Luma of the missing last pixel
+            assert_eq!(uyvy[11], 0);
+
+            for pair in 0..3 {
+                let (u, v) = if pair < 2 {
+                    (
+                        avg(
+                            pattern(0, line, 8 * pair + 2),
+                            pattern(0, line, 8 * pair + 6),
+                        ),
+                        avg(
+                            pattern(0, line, 8 * pair + 3),
+                            pattern(0, line, 8 * pair + 7),
+                        ),
+                    )
+                } else {
+                    (
+                        pattern(0, line, 8 * pair + 2),
+                        pattern(0, line, 8 * pair + 3),
+                    )
+                };
+                assert_eq!(uyvy[pair * 4], u);
+                assert_eq!(uyvy[pair * 4 + 2], v);
+            }
+        }
+    }
+
+    #[test]
+    fn send_instance_invalid_name() {
+        let res = create_send_instance("ndi\0sink".to_string(), None, true, false);
+        assert!(match res {
+            Err(SendCreateError::InvalidName) => true,
+            _ => false,
+        });
+    }
+
+    #[test]
+    fn send_instance_invalid_groups() {
+        let res = create_send_instance("ndisink".to_string(), Some("public\0"), true, false);
+        assert!(match res {
+            Err(SendCreateError::InvalidGroups) => true,
+            _ => false,
+        });
+    }
+
+    #[test]
+    fn unsupported_video_format() {
+        gst::init().unwrap();
+
+        let res = create_ndi_send_video_frame(
+            320,
+            240,
+            NDIlib_frame_format_type_e::NDIlib_frame_format_type_progressive,
+        )
+        .with_format(gst_video::VideoFormat::Gray8);
+        assert!(match res {
+            Err(SendCreateError::UnsupportedVideoFormat(gst_video::VideoFormat::Gray8)) => true,
+            _ => false,
+        });
+    }
+
+    #[test]
+    fn unsupported_audio_format() {
+        gst::init().unwrap();
+
+        let info = gst_audio::AudioInfo::new(gst_audio::AudioFormat::U8, 48000, 2)
+            .build()
+            .unwrap();
+        let buffer = gst::Buffer::with_size(8).unwrap();
+
+        let res = create_ndi_send_audio_frame_from_buffer(&info, buffer.as_ref(), 0);
+        assert!(match res {
+            Err(SendCreateError::UnsupportedAudioFormat(gst_audio::AudioFormat::U8)) => true,
+            _ => false,
+        });
+    }
+}